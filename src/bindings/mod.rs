@@ -0,0 +1,120 @@
+//! Keys of the Lynx buttons and the hotkeys, from the defaults, the bindings
+//! file and `--buttons` or `--hotkeys`, in that order.
+
+use crate::{
+    hotkeys::{Hotkey, HotkeyMapping},
+    keycodes::KeyChord,
+    runner::runner_config::Input,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub(crate) mod panel;
+
+pub(crate) type ButtonMapping = HashMap<KeyChord, Input>;
+
+/// Hotkeys are prefixed with this in the bindings file.
+pub(crate) const HOTKEY_PREFIX: &str = "hotkey.";
+
+/// `<name>=<key>|<key>...`, no keys to leave it unbound.
+pub(crate) fn parse<T: FromStr<Err = String>>(entry: &str) -> Result<(T, Vec<KeyChord>), String> {
+    let (name, keys) = entry
+        .split_once('=')
+        .ok_or_else(|| format!("Expected <name>=<keys>, got '{entry}'."))?;
+    let action: T = name.trim().parse()?;
+    let keys = keys
+        .split('|')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<KeyChord>, String>>()?;
+    Ok((action, keys))
+}
+
+/// Replaces the keys of a button or hotkey.
+pub(crate) fn apply<T: FromStr<Err = String> + Copy + PartialEq>(
+    mapping: &mut HashMap<KeyChord, T>,
+    entry: &str,
+) -> Result<(), String> {
+    let (action, keys) = parse(entry)?;
+    mapping.retain(|_, a| *a != action);
+    for key in keys {
+        mapping.insert(key, action);
+    }
+    Ok(())
+}
+
+/// Keys of a button or hotkey, in a stable order.
+pub(crate) fn keys<T: PartialEq>(mapping: &HashMap<KeyChord, T>, action: T) -> Vec<KeyChord> {
+    let mut keys: Vec<KeyChord> = mapping
+        .iter()
+        .filter(|(_, a)| **a == action)
+        .map(|(key, _)| *key)
+        .collect();
+    keys.sort_by_key(ToString::to_string);
+    keys
+}
+
+fn line<T: PartialEq>(name: &str, mapping: &HashMap<KeyChord, T>, action: T) -> String {
+    let keys: Vec<String> = keys(mapping, action)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    format!("{name}={}\n", keys.join("|"))
+}
+
+/// `holani-min` in the user configuration directory.
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    #[cfg(windows)]
+    let base = std::env::var_os("APPDATA").map(PathBuf::from);
+    #[cfg(not(windows))]
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    base.map(|base| base.join("holani-min"))
+}
+
+/// `bindings.cfg` in the user configuration directory.
+pub(crate) fn path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("bindings.cfg"))
+}
+
+/// A missing file is no bindings.
+pub(crate) fn load(path: &Path) -> Result<Vec<String>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(data) => Ok(data
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(format!("Couldn't read '{}': {err}.", path.display())),
+    }
+}
+
+pub(crate) fn save(
+    path: &Path,
+    buttons: &ButtonMapping,
+    hotkeys: &HotkeyMapping,
+) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|err| format!("Couldn't create '{}': {err}.", dir.display()))?;
+    }
+    let mut data = String::from("# holani-min key bindings, <button>=<key>|<key>...\n");
+    for input in Input::ALL {
+        data.push_str(&line(input.name(), buttons, input));
+    }
+    for hotkey in Hotkey::ALL {
+        data.push_str(&line(
+            &format!("{HOTKEY_PREFIX}{}", hotkey.name()),
+            hotkeys,
+            hotkey,
+        ));
+    }
+    std::fs::write(path, data).map_err(|err| format!("Couldn't write '{}': {err}.", path.display()))
+}
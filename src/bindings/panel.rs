@@ -0,0 +1,247 @@
+use super::ButtonMapping;
+use crate::{
+    hotkeys::HotkeyMapping,
+    keycodes::{is_modifier, keycode_name, KeyChord, Modifiers},
+    overlay::Overlay,
+    runner::runner_config::Input,
+};
+use macroquad::prelude::*;
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F6;
+const FONT_SIZE: f32 = 16.;
+const LINE_HEIGHT: f32 = 16.;
+const MARGIN: f32 = 8.;
+const HELP: &str = "F6 close  Up/Down select  Enter set key  A add key  Del clear";
+/// Keys the overlays need for themselves.
+const RESERVED: [KeyCode; 7] = [
+    KeyCode::Escape,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+];
+
+#[derive(Clone, Copy)]
+enum State {
+    Browsing,
+    /// Waiting for a key, to replace the keys of the button or add one.
+    Capturing {
+        add: bool,
+        modifier: Option<KeyCode>,
+    },
+    /// The key is bound to another button, waiting for confirmation.
+    Conflict {
+        chord: KeyChord,
+        add: bool,
+    },
+}
+
+/// Lists the keys of each Lynx button and rebinds them. The mappings are
+/// shared with the input loop and saved to the bindings file on every change.
+pub(crate) struct BindingsPanel {
+    mapping: Rc<RefCell<ButtonMapping>>,
+    hotkeys: Rc<RefCell<HotkeyMapping>>,
+    path: Option<PathBuf>,
+    visible: bool,
+    selected: usize,
+    state: State,
+    message: String,
+}
+
+impl BindingsPanel {
+    pub(crate) fn new(
+        mapping: Rc<RefCell<ButtonMapping>>,
+        hotkeys: Rc<RefCell<HotkeyMapping>>,
+    ) -> Self {
+        Self {
+            mapping,
+            hotkeys,
+            path: super::path(),
+            visible: false,
+            selected: 0,
+            state: State::Browsing,
+            message: String::new(),
+        }
+    }
+
+    fn input(&self) -> Input {
+        Input::ALL[self.selected]
+    }
+
+    fn browse(&mut self) {
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1).min(Input::ALL.len() - 1);
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            self.state = State::Capturing {
+                add: false,
+                modifier: None,
+            };
+        }
+        if is_key_pressed(KeyCode::A) {
+            self.state = State::Capturing {
+                add: true,
+                modifier: None,
+            };
+        }
+        if is_key_pressed(KeyCode::Delete) || is_key_pressed(KeyCode::Backspace) {
+            let input = self.input();
+            self.mapping.borrow_mut().retain(|_, i| *i != input);
+            self.changed(format!("{} cleared.", input.name()));
+        }
+    }
+
+    /// A modifier alone is bound when it is released without another key.
+    fn capture(&mut self, add: bool, modifier: Option<KeyCode>) {
+        let chord = match (get_last_key_pressed(), modifier) {
+            (Some(KeyCode::Escape), _) => {
+                self.state = State::Browsing;
+                return;
+            }
+            (Some(key), _) if is_modifier(key) => {
+                self.state = State::Capturing {
+                    add,
+                    modifier: Some(key),
+                };
+                return;
+            }
+            (Some(key), _) => KeyChord {
+                key,
+                modifiers: Modifiers::held(),
+            },
+            (None, Some(modifier)) if is_key_released(modifier) => KeyChord::new(modifier),
+            (None, _) => return,
+        };
+
+        if RESERVED.contains(&chord.key) {
+            self.message = format!(
+                "{} is used by the overlays.",
+                keycode_name(chord.key).unwrap_or("?")
+            );
+            self.state = State::Browsing;
+            return;
+        }
+        if let Some(hotkey) = self.hotkeys.borrow().get(&chord) {
+            self.message = format!("{chord} is the {} hotkey.", hotkey.name());
+            self.state = State::Browsing;
+            return;
+        }
+        let bound = self.mapping.borrow().get(&chord).copied();
+        match bound {
+            Some(input) if input != self.input() => {
+                self.message = format!(
+                    "{chord} is bound to {}: Enter to move it, Esc to keep it.",
+                    input.name()
+                );
+                self.state = State::Conflict { chord, add };
+            }
+            _ => self.bind(chord, add),
+        }
+    }
+
+    fn bind(&mut self, chord: KeyChord, add: bool) {
+        let input = self.input();
+        {
+            let mut mapping = self.mapping.borrow_mut();
+            if !add {
+                mapping.retain(|_, i| *i != input);
+            }
+            mapping.insert(chord, input);
+        }
+        self.state = State::Browsing;
+        self.changed(format!("{chord} bound to {}.", input.name()));
+    }
+
+    fn changed(&mut self, message: String) {
+        self.message = match &self.path {
+            None => format!("{message} Not saved, no configuration directory."),
+            Some(path) => match super::save(path, &self.mapping.borrow(), &self.hotkeys.borrow()) {
+                Ok(()) => format!("{message} Saved to {}.", path.display()),
+                Err(err) => format!("{message} Not saved: {err}"),
+            },
+        };
+    }
+}
+
+impl Overlay for BindingsPanel {
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn update(&mut self) -> bool {
+        if matches!(self.state, State::Browsing) && is_key_pressed(TOGGLE_KEY) {
+            self.visible = !self.visible;
+            // Keys pressed before opening aren't bindings.
+            while get_char_pressed().is_some() {}
+        }
+        if !self.visible {
+            return false;
+        }
+
+        match self.state {
+            State::Browsing => self.browse(),
+            State::Capturing { add, modifier } => self.capture(add, modifier),
+            State::Conflict { chord, add } => {
+                if is_key_pressed(KeyCode::Enter) {
+                    self.bind(chord, add);
+                } else if is_key_pressed(KeyCode::Escape) {
+                    self.message.clear();
+                    self.state = State::Browsing;
+                }
+            }
+        }
+        // Typed characters belong to the panel.
+        while get_char_pressed().is_some() {}
+        true
+    }
+
+    fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        draw_rectangle(
+            0.,
+            0.,
+            screen_width(),
+            screen_height(),
+            Color::new(0., 0., 0., 0.85),
+        );
+        let text_y = |row: usize| MARGIN + LINE_HEIGHT * (row as f32 + 1.) - 4.;
+
+        draw_text(HELP, MARGIN, text_y(0), FONT_SIZE, WHITE);
+        let prompt = match self.state {
+            State::Capturing { .. } => {
+                format!("Press a key for {}, Esc to cancel", self.input().name())
+            }
+            _ => self.message.clone(),
+        };
+        draw_text(&prompt, MARGIN, text_y(1), FONT_SIZE, GRAY);
+
+        let mapping = self.mapping.borrow();
+        for (row, input) in Input::ALL.iter().enumerate() {
+            let keys: Vec<String> = super::keys(&mapping, *input)
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            let color = if row == self.selected { YELLOW } else { WHITE };
+            let keys = if keys.is_empty() {
+                "-".to_string()
+            } else {
+                keys.join(" | ")
+            };
+            draw_text(
+                &format!("{:<14} {keys}", input.name()),
+                MARGIN,
+                text_y(row + 3),
+                FONT_SIZE,
+                color,
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::debugger::cpu;
+use holani::lynx::Lynx;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub(crate) mod panel;
+pub(crate) mod search;
+
+/// A byte forced at an address every frame, optionally only when the address
+/// holds the `compare` value.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Cheat {
+    pub addr: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    pub(crate) fn apply(&self, lynx: &mut Lynx) {
+        if !self.enabled {
+            return;
+        }
+        if self
+            .compare
+            .is_none_or(|compare| cpu::peek(lynx, self.addr) == compare)
+        {
+            cpu::poke(lynx, self.addr, self.value);
+        }
+    }
+}
+
+/// `[-]AAAA:VV[?CC]`, hexadecimal, a leading `-` for a disabled cheat.
+impl FromStr for Cheat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid cheat '{s}', expected AAAA:VV[?CC].");
+        let (enabled, code) = match s.trim().strip_prefix('-') {
+            Some(code) => (false, code),
+            None => (true, s.trim()),
+        };
+        let (addr, value) = code.split_once(':').ok_or_else(invalid)?;
+        let (value, compare) = match value.split_once('?') {
+            Some((value, compare)) => (value, Some(compare)),
+            None => (value, None),
+        };
+        Ok(Self {
+            addr: u16::from_str_radix(addr, 16).map_err(|_| invalid())?,
+            value: u8::from_str_radix(value, 16).map_err(|_| invalid())?,
+            compare: compare
+                .map(|compare| u8::from_str_radix(compare, 16))
+                .transpose()
+                .map_err(|_| invalid())?,
+            enabled,
+        })
+    }
+}
+
+impl fmt::Display for Cheat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.enabled {
+            write!(f, "-")?;
+        }
+        write!(f, "{:04X}:{:02X}", self.addr, self.value)?;
+        if let Some(compare) = self.compare {
+            write!(f, "?{compare:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Cheats are stored next to the cartridge, one per line.
+pub(crate) fn cheats_path(cartridge: &Path) -> PathBuf {
+    cartridge.with_extension("cht")
+}
+
+pub(crate) fn load(path: &Path) -> Result<Vec<Cheat>, String> {
+    let Ok(data) = std::fs::read_to_string(path) else {
+        return Ok(vec![]);
+    };
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(Cheat::from_str)
+        .collect()
+}
+
+pub(crate) fn save(path: &Path, cheats: &[Cheat]) -> std::io::Result<()> {
+    let data: String = cheats.iter().map(|cheat| format!("{cheat}\n")).collect();
+    std::fs::write(path, data)
+}
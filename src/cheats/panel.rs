@@ -0,0 +1,323 @@
+use super::{
+    search::{Filter, RamSearch},
+    Cheat,
+};
+use crate::{overlay::Overlay, runner::RunnerCommand};
+use macroquad::prelude::*;
+use std::path::{Path, PathBuf};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+const FONT_SIZE: f32 = 16.;
+const LINE_HEIGHT: f32 = 16.;
+const MARGIN: f32 = 8.;
+const HELP: &str = "F3 close  Tab switch list";
+const SEARCH_HELP: &str =
+    "N new  =VV equal  > greater  < less  C changed  U unchanged  A add cheat";
+const CHEATS_HELP: &str = "Space on/off  +/- value  Del remove";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Search,
+    Cheats,
+}
+
+/// RAM search and cheat list drawn over the emulation. Cheats are sent to the
+/// runner thread, which forces them every frame, and saved next to the
+/// cartridge on every change.
+pub(crate) struct CheatPanel {
+    command_tx: kanal::Sender<RunnerCommand>,
+    memory_tx: kanal::Sender<Vec<u8>>,
+    memory_rx: kanal::Receiver<Vec<u8>>,
+    path: PathBuf,
+    visible: bool,
+    requested: bool,
+    memory: Vec<u8>,
+    search: Option<RamSearch>,
+    cheats: Vec<Cheat>,
+    focus: Focus,
+    selected_candidate: usize,
+    selected_cheat: usize,
+    value: Option<String>,
+    message: String,
+}
+
+impl CheatPanel {
+    pub(crate) fn new(command_tx: kanal::Sender<RunnerCommand>, cartridge: &Path) -> Self {
+        let (memory_tx, memory_rx) = kanal::bounded::<Vec<u8>>(1);
+        let path = super::cheats_path(cartridge);
+        let cheats = super::load(&path).unwrap_or_else(|err| {
+            println!("Cheats not loaded: {err}");
+            vec![]
+        });
+        if !cheats.is_empty() {
+            println!("{} cheat(s) loaded from {}.", cheats.len(), path.display());
+            let _ = command_tx.send(RunnerCommand::SetCheats(cheats.clone()));
+        }
+        Self {
+            command_tx,
+            memory_tx,
+            memory_rx,
+            path,
+            visible: false,
+            requested: false,
+            memory: vec![],
+            search: None,
+            cheats,
+            focus: Focus::Search,
+            selected_candidate: 0,
+            selected_cheat: 0,
+            value: None,
+            message: String::new(),
+        }
+    }
+
+    fn rows() -> usize {
+        ((screen_height() - MARGIN * 2.) / LINE_HEIGHT - 4.).max(1.) as usize
+    }
+
+    fn keys(&mut self) {
+        if is_key_pressed(KeyCode::Tab) {
+            self.focus = match self.focus {
+                Focus::Search => Focus::Cheats,
+                Focus::Cheats => Focus::Search,
+            };
+        }
+        let (selected, len) = match self.focus {
+            Focus::Search => (
+                &mut self.selected_candidate,
+                self.search.as_ref().map_or(0, |s| s.candidates().len()),
+            ),
+            Focus::Cheats => (&mut self.selected_cheat, self.cheats.len()),
+        };
+        if is_key_pressed(KeyCode::Up) {
+            *selected = selected.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            *selected += 1;
+        }
+        *selected = (*selected).min(len.saturating_sub(1));
+
+        if is_key_pressed(KeyCode::Escape) {
+            self.value = None;
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            if let Some(value) = self.value.take() {
+                match u8::from_str_radix(&value, 16) {
+                    Ok(value) => self.filter(Filter::Equal(value)),
+                    Err(_) => self.message = format!("Invalid value '{value}'."),
+                }
+            }
+        }
+        if is_key_pressed(KeyCode::Delete) && self.focus == Focus::Cheats {
+            if self.selected_cheat < self.cheats.len() {
+                self.cheats.remove(self.selected_cheat);
+                self.cheats_changed();
+            }
+        }
+
+        while let Some(c) = get_char_pressed() {
+            if let Some(value) = self.value.as_mut() {
+                if c.is_ascii_hexdigit() && value.len() < 2 {
+                    value.push(c.to_ascii_uppercase());
+                }
+                continue;
+            }
+            match (self.focus, c.to_ascii_lowercase()) {
+                (Focus::Search, 'n') => self.new_search(),
+                (Focus::Search, '=') => self.value = Some(String::new()),
+                (Focus::Search, '>') => self.filter(Filter::Greater),
+                (Focus::Search, '<') => self.filter(Filter::Less),
+                (Focus::Search, 'c') => self.filter(Filter::Changed),
+                (Focus::Search, 'u') => self.filter(Filter::Unchanged),
+                (Focus::Search, 'a') => self.add_cheat(),
+                (Focus::Cheats, ' ') => self.edit_cheat(|cheat| cheat.enabled = !cheat.enabled),
+                (Focus::Cheats, '+') => {
+                    self.edit_cheat(|cheat| cheat.value = cheat.value.wrapping_add(1));
+                }
+                (Focus::Cheats, '-') => {
+                    self.edit_cheat(|cheat| cheat.value = cheat.value.wrapping_sub(1));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn new_search(&mut self) {
+        if self.memory.is_empty() {
+            return;
+        }
+        self.search = Some(RamSearch::new(&self.memory));
+        self.selected_candidate = 0;
+        self.message = "New search.".to_string();
+    }
+
+    fn filter(&mut self, filter: Filter) {
+        if self.memory.is_empty() {
+            return;
+        }
+        let search = self
+            .search
+            .get_or_insert_with(|| RamSearch::new(&self.memory));
+        search.filter(&self.memory, filter);
+        self.selected_candidate = 0;
+        self.message = format!("{} candidate(s).", search.candidates().len());
+    }
+
+    fn add_cheat(&mut self) {
+        let Some(addr) = self
+            .search
+            .as_ref()
+            .and_then(|search| search.candidates().get(self.selected_candidate).copied())
+        else {
+            return;
+        };
+        let value = self.memory[usize::from(addr)];
+        self.cheats.retain(|cheat| cheat.addr != addr);
+        self.cheats.push(Cheat {
+            addr,
+            value,
+            compare: None,
+            enabled: true,
+        });
+        self.selected_cheat = self.cheats.len() - 1;
+        self.cheats_changed();
+    }
+
+    fn edit_cheat(&mut self, edit: impl FnOnce(&mut Cheat)) {
+        if let Some(cheat) = self.cheats.get_mut(self.selected_cheat) {
+            edit(cheat);
+            self.cheats_changed();
+        }
+    }
+
+    fn cheats_changed(&mut self) {
+        let _ = self
+            .command_tx
+            .send(RunnerCommand::SetCheats(self.cheats.clone()));
+        self.message = match super::save(&self.path, &self.cheats) {
+            Ok(()) => format!("Cheats saved to {}.", self.path.display()),
+            Err(err) => format!("Cheats not saved: {err}."),
+        };
+    }
+}
+
+impl Overlay for CheatPanel {
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn update(&mut self) -> bool {
+        if is_key_pressed(TOGGLE_KEY) {
+            self.visible = !self.visible;
+            self.value = None;
+        }
+        if !self.visible {
+            return false;
+        }
+
+        if let Ok(Some(memory)) = self.memory_rx.try_recv() {
+            self.memory = memory;
+            self.requested = false;
+        }
+        if !self.requested {
+            self.requested = self
+                .command_tx
+                .send(RunnerCommand::ReadMemory(
+                    0,
+                    0x1_0000,
+                    self.memory_tx.clone(),
+                ))
+                .is_ok();
+        }
+
+        self.keys();
+        true
+    }
+
+    fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        draw_rectangle(
+            0.,
+            0.,
+            screen_width(),
+            screen_height(),
+            Color::new(0., 0., 0., 0.85),
+        );
+        let text_y = |row: usize| MARGIN + LINE_HEIGHT * (row as f32 + 1.) - 4.;
+        let column_x = screen_width() / 2.;
+        let title_color = |focus| if self.focus == focus { YELLOW } else { GRAY };
+
+        draw_text(HELP, MARGIN, text_y(0), FONT_SIZE, WHITE);
+        let help = match (&self.value, self.focus) {
+            (Some(value), _) => format!("Equal to ${value}_"),
+            (None, Focus::Search) => SEARCH_HELP.to_string(),
+            (None, Focus::Cheats) => CHEATS_HELP.to_string(),
+        };
+        draw_text(&help, MARGIN, text_y(1), FONT_SIZE, WHITE);
+        draw_text(&self.message, MARGIN, text_y(2), FONT_SIZE, GRAY);
+
+        let candidates = self.search.as_ref().map_or(&[][..], |s| s.candidates());
+        draw_text(
+            &format!("Search, {} candidate(s)", candidates.len()),
+            MARGIN,
+            text_y(3),
+            FONT_SIZE,
+            title_color(Focus::Search),
+        );
+        let first = self.selected_candidate.saturating_sub(Self::rows() / 2);
+        for (row, (i, addr)) in candidates
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(Self::rows())
+            .enumerate()
+        {
+            let previous = self.search.as_ref().map_or(0, |s| s.previous(*addr));
+            let current = self.memory.get(usize::from(*addr)).copied().unwrap_or(0);
+            let color = if i == self.selected_candidate && self.focus == Focus::Search {
+                YELLOW
+            } else {
+                WHITE
+            };
+            draw_text(
+                &format!("${addr:04X}  {previous:02X} -> {current:02X}"),
+                MARGIN,
+                text_y(row + 4),
+                FONT_SIZE,
+                color,
+            );
+        }
+
+        draw_text(
+            "Cheats",
+            column_x,
+            text_y(3),
+            FONT_SIZE,
+            title_color(Focus::Cheats),
+        );
+        for (i, cheat) in self.cheats.iter().enumerate().take(Self::rows()) {
+            let color = match (
+                i == self.selected_cheat && self.focus == Focus::Cheats,
+                cheat.enabled,
+            ) {
+                (true, _) => YELLOW,
+                (false, true) => WHITE,
+                (false, false) => GRAY,
+            };
+            let state = if cheat.enabled { "on " } else { "off" };
+            let code = Cheat {
+                enabled: true,
+                ..*cheat
+            };
+            draw_text(
+                &format!("{state}  {code}"),
+                column_x,
+                text_y(i + 4),
+                FONT_SIZE,
+                color,
+            );
+        }
+    }
+}
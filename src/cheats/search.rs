@@ -0,0 +1,51 @@
+/// Addresses searched, the RAM below the Suzy registers.
+const RAM_END: usize = 0xFC00;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Filter {
+    Equal(u8),
+    Greater,
+    Less,
+    Changed,
+    Unchanged,
+}
+
+/// RAM search narrowing down the addresses holding a value, comparing
+/// memory snapshots taken between the filters.
+pub(crate) struct RamSearch {
+    candidates: Vec<u16>,
+    previous: Vec<u8>,
+}
+
+impl RamSearch {
+    pub(crate) fn new(memory: &[u8]) -> Self {
+        Self {
+            candidates: (0u16..).take(RAM_END).collect(),
+            previous: memory[..RAM_END].to_vec(),
+        }
+    }
+
+    pub(crate) fn filter(&mut self, memory: &[u8], filter: Filter) {
+        let previous = &self.previous;
+        self.candidates.retain(|addr| {
+            let (old, new) = (previous[usize::from(*addr)], memory[usize::from(*addr)]);
+            match filter {
+                Filter::Equal(value) => new == value,
+                Filter::Greater => new > old,
+                Filter::Less => new < old,
+                Filter::Changed => new != old,
+                Filter::Unchanged => new == old,
+            }
+        });
+        self.previous = memory[..RAM_END].to_vec();
+    }
+
+    pub(crate) fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    /// Value of `addr` when the last filter was applied.
+    pub(crate) fn previous(&self, addr: u16) -> u8 {
+        self.previous[usize::from(addr)]
+    }
+}
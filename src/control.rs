@@ -0,0 +1,292 @@
+//! Remote control of the emulation for test harnesses: a JSON-lines server on
+//! a local socket, one request object per line, one response per line.
+//!
+//! ```text
+//! {"cmd":"pause"}                          {"ok":true}
+//! {"cmd":"resume"}                         {"ok":true}
+//! {"cmd":"step","frames":10}               {"ok":true}  once paused again
+//! {"cmd":"buttons","pressed":["up","inside"]}
+//! {"cmd":"read","addr":512,"len":4}        {"ok":true,"data":"a9008d10"}
+//! {"cmd":"write","addr":512,"data":"ea"}
+//! {"cmd":"screen"}                         {"ok":true,"width":160,"height":102,"rgba":"..."}
+//! {"cmd":"screen","path":"shot.png"}
+//! {"cmd":"save_state","path":"a.state"}
+//! {"cmd":"load_state","path":"a.state"}
+//! {"cmd":"reset"}
+//! {"cmd":"power_cycle"}
+//! ```
+//!
+//! Failures are answered with `{"ok":false,"error":"..."}`. Requests are
+//! turned into runner commands, executed by the runner thread between ticks.
+
+use crate::{
+    debugger::gdb::{from_hex, to_hex},
+    runner::{runner_config::Input, RunnerCommand},
+};
+use holani::{
+    mikey::video::{LYNX_SCREEN_HEIGHT, LYNX_SCREEN_WIDTH},
+    suzy::registers::{Joystick, Switches},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::PathBuf,
+    str::FromStr,
+};
+#[cfg(unix)]
+use std::{
+    os::unix::{fs::FileTypeExt, net::UnixListener},
+    path::Path,
+};
+use thread_priority::{ThreadBuilderExt, ThreadPriority};
+
+const NOT_RUNNING: &str = "The emulation isn't running.";
+
+#[derive(Clone)]
+pub(crate) enum ControlEndpoint {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl FromStr for ControlEndpoint {
+    type Err = String;
+
+    /// `<port>` on localhost, `tcp://<host>:<port>` or `unix:<path>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(port) = s.parse::<u16>() {
+            return Ok(ControlEndpoint::Tcp(format!("127.0.0.1:{port}")));
+        }
+        if let Some(addr) = s.strip_prefix("tcp://") {
+            return Ok(ControlEndpoint::Tcp(addr.to_string()));
+        }
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(ControlEndpoint::Unix(PathBuf::from(
+                path.strip_prefix("//").unwrap_or(path),
+            )));
+            #[cfg(not(unix))]
+            return Err(format!(
+                "Unix domain sockets are not supported on this platform ({path})."
+            ));
+        }
+        Err(format!("Invalid control endpoint '{s}'."))
+    }
+}
+
+fn one<T: From<u8>>() -> T {
+    T::from(1)
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Request {
+    Pause,
+    Resume,
+    Step {
+        #[serde(default = "one")]
+        frames: u32,
+    },
+    Buttons {
+        #[serde(default)]
+        pressed: Vec<String>,
+    },
+    Read {
+        addr: u16,
+        #[serde(default = "one")]
+        len: usize,
+    },
+    Write {
+        addr: u16,
+        data: String,
+    },
+    Screen {
+        path: Option<PathBuf>,
+    },
+    SaveState {
+        path: PathBuf,
+    },
+    LoadState {
+        path: PathBuf,
+    },
+    Reset,
+    PowerCycle,
+}
+
+/// Sends a command carrying a reply channel and waits for the reply.
+fn call<T>(
+    command_tx: &kanal::Sender<RunnerCommand>,
+    command: impl FnOnce(kanal::Sender<T>) -> RunnerCommand,
+) -> Result<T, String> {
+    let (reply_tx, reply_rx) = kanal::bounded::<T>(1);
+    command_tx
+        .send(command(reply_tx))
+        .map_err(|_| NOT_RUNNING.to_string())?;
+    reply_rx
+        .recv()
+        .map_err(|_| "The request was cancelled.".to_string())
+}
+
+fn send(command_tx: &kanal::Sender<RunnerCommand>, command: RunnerCommand) -> Result<(), String> {
+    command_tx
+        .send(command)
+        .map_err(|_| NOT_RUNNING.to_string())
+}
+
+fn execute(request: Request, command_tx: &kanal::Sender<RunnerCommand>) -> Result<Value, String> {
+    match request {
+        Request::Pause => send(command_tx, RunnerCommand::Pause(true))?,
+        Request::Resume => send(command_tx, RunnerCommand::Pause(false))?,
+        Request::Step { frames } => {
+            call(command_tx, |reply_tx| {
+                RunnerCommand::StepFrames(frames, reply_tx)
+            })?;
+        }
+        Request::Buttons { pressed } => {
+            let mut joystick = Joystick::empty();
+            let mut switches = Switches::empty();
+            for name in pressed {
+                name.parse::<Input>()?
+                    .set(&mut joystick, &mut switches, true);
+            }
+            send(
+                command_tx,
+                RunnerCommand::SetButtons(joystick.bits(), switches.bits()),
+            )?;
+        }
+        Request::Read { addr, len } => {
+            let data = call(command_tx, |reply_tx| {
+                RunnerCommand::ReadMemory(addr, len.min(0x1_0000), reply_tx)
+            })?;
+            return Ok(json!({ "ok": true, "data": to_hex(&data) }));
+        }
+        Request::Write { addr, data } => {
+            let data = from_hex(&data).ok_or("Invalid hexadecimal data.")?;
+            for (offset, value) in (0..=u16::MAX).zip(data) {
+                send(
+                    command_tx,
+                    RunnerCommand::Poke(addr.wrapping_add(offset), value),
+                )?;
+            }
+        }
+        Request::Screen { path } => {
+            let rgba = call(command_tx, RunnerCommand::ReadScreen)?;
+            if let Some(path) = path {
+                crate::screenshot::save(&path, &rgba)?;
+            } else {
+                return Ok(json!({
+                    "ok": true,
+                    "width": LYNX_SCREEN_WIDTH,
+                    "height": LYNX_SCREEN_HEIGHT,
+                    "rgba": to_hex(&rgba),
+                }));
+            }
+        }
+        Request::SaveState { path } => {
+            call(command_tx, |reply_tx| {
+                RunnerCommand::SaveState(path, reply_tx)
+            })??;
+        }
+        Request::LoadState { path } => {
+            call(command_tx, |reply_tx| {
+                RunnerCommand::LoadState(path, reply_tx)
+            })??;
+        }
+        Request::Reset => call(command_tx, RunnerCommand::Reset)??,
+        Request::PowerCycle => call(command_tx, RunnerCommand::PowerCycle)??,
+    }
+    Ok(json!({ "ok": true }))
+}
+
+fn serve(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    command_tx: &kanal::Sender<RunnerCommand>,
+) -> std::io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = serde_json::from_str::<Request>(&line)
+            .map_err(|err| format!("Invalid request: {err}."))
+            .and_then(|request| execute(request, command_tx))
+            .unwrap_or_else(|err| json!({ "ok": false, "error": err }));
+        writeln!(writer, "{response}")?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Listens on a Unix domain socket, replacing the one a previous run left
+/// behind. Any other file at `path` is kept and makes it fail.
+#[cfg(unix)]
+pub(crate) fn bind_unix(path: &Path) -> std::io::Result<UnixListener> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "not a socket",
+            ))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    UnixListener::bind(path)
+}
+
+/// Serves control clients, one at a time, until the emulation stops.
+pub(crate) fn spawn(endpoint: ControlEndpoint, command_tx: kanal::Sender<RunnerCommand>) {
+    std::thread::Builder::new()
+        .name("Control".to_string())
+        .spawn_with_priority(ThreadPriority::Min, move |_| match endpoint {
+            ControlEndpoint::Tcp(addr) => {
+                let listener = TcpListener::bind(&addr).unwrap_or_else(|err| {
+                    println!("Error: Couldn't listen on {addr}: {err}.");
+                    std::process::exit(1);
+                });
+                println!("Control server listening on {addr}");
+                for stream in listener.incoming() {
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    let _ = stream.set_nodelay(true);
+                    let Ok(reader) = stream.try_clone() else {
+                        continue;
+                    };
+                    if let Err(e) = serve(BufReader::new(reader), stream, &command_tx) {
+                        println!("Control client disconnected: {e}.");
+                    }
+                    if command_tx.is_disconnected() {
+                        return;
+                    }
+                }
+            }
+            #[cfg(unix)]
+            ControlEndpoint::Unix(path) => {
+                let listener = bind_unix(&path).unwrap_or_else(|err| {
+                    println!("Error: Couldn't listen on {}: {err}.", path.display());
+                    std::process::exit(1);
+                });
+                println!("Control server listening on {}", path.display());
+                for stream in listener.incoming() {
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    let Ok(reader) = stream.try_clone() else {
+                        continue;
+                    };
+                    if let Err(e) = serve(BufReader::new(reader), stream, &command_tx) {
+                        println!("Control client disconnected: {e}.");
+                    }
+                    if command_tx.is_disconnected() {
+                        return;
+                    }
+                }
+            }
+        })
+        .expect("Could not create the control server thread.");
+}
@@ -0,0 +1,95 @@
+//! Access to the CPU state and address space of a running `Lynx`.
+//!
+//! holani executes each instruction on the tick it completes, so the program
+//! counter only moves on instruction boundaries. The debugger relies on that
+//! to notice a new instruction is about to run; an instruction jumping onto
+//! itself doesn't move it and is only seen once.
+
+use holani::lynx::Lynx;
+use std::fmt;
+
+pub(crate) const FLAG_NAMES: [char; 8] = ['C', 'Z', 'I', 'D', 'B', '-', 'V', 'N'];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Registers {
+    pub pc: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub s: u8,
+    pub p: u8,
+}
+
+impl Registers {
+    pub(crate) fn read(lynx: &Lynx) -> Self {
+        let cpu = lynx.cpu();
+        Self {
+            pc: cpu.pc(),
+            a: cpu.a(),
+            x: cpu.x(),
+            y: cpu.y(),
+            s: cpu.s(),
+            p: cpu.p(),
+        }
+    }
+
+    pub(crate) fn write(&self, lynx: &mut Lynx) {
+        let cpu = lynx.cpu_mut();
+        cpu.set_pc(self.pc);
+        cpu.set_a(self.a);
+        cpu.set_x(self.x);
+        cpu.set_y(self.y);
+        cpu.set_s(self.s);
+        cpu.set_p(self.p);
+    }
+
+    pub(crate) fn flags(&self) -> String {
+        FLAG_NAMES
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(bit, name)| {
+                if self.p & (1 << bit) != 0 {
+                    *name
+                } else {
+                    name.to_ascii_lowercase()
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PC=${:04X} A=${:02X} X=${:02X} Y=${:02X} SP=$01{:02X} P=${:02X} [{}]",
+            self.pc,
+            self.a,
+            self.x,
+            self.y,
+            self.s,
+            self.p,
+            self.flags()
+        )
+    }
+}
+
+/// Reads `addr` as the CPU currently sees it, without side effects on the
+/// hardware registers.
+pub(crate) fn peek(lynx: &Lynx, addr: u16) -> u8 {
+    lynx.peek(addr)
+}
+
+/// Reads `len` bytes from `addr`, wrapping around the address space.
+pub(crate) fn peek_range(lynx: &Lynx, addr: u16, len: usize) -> Vec<u8> {
+    (0..=u16::MAX)
+        .take(len)
+        .map(|offset| peek(lynx, addr.wrapping_add(offset)))
+        .collect()
+}
+
+/// Writes `addr` as the CPU currently sees it.
+pub(crate) fn poke(lynx: &mut Lynx, addr: u16, data: u8) {
+    lynx.poke(addr, data);
+}
@@ -0,0 +1,145 @@
+use super::{
+    cpu, cpu::Registers, opcodes::disassemble_range, repl::parse_address, symbols::Symbols,
+};
+use crate::{
+    gamedb,
+    info::{self, BLL_HEADER_SIZE, LNX_HEADER_SIZE, PAGES_PER_BANK},
+    lyx::BankSizes,
+    runner::{load_lynx, run_frame},
+};
+use clap::Args;
+use holani::lynx::Lynx;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub(crate) struct DisasmArgs {
+    /// Cartridge, can be .o, .lnx or a raw .lyx file
+    cartridge: PathBuf,
+
+    /// ROM override
+    #[arg(short, long)]
+    rom: Option<PathBuf>,
+
+    /// Bank sizes of a raw .lyx dump <BANK0>[,<BANK1>], in bytes or K [default: from the dump size]
+    #[arg(long, value_name = "SIZE")]
+    bank_size: Option<BankSizes>,
+
+    /// Frames to run before disassembling, for the boot loader to copy the code into RAM
+    #[arg(long, value_name = "FRAMES", default_value_t = 30)]
+    frames: u32,
+
+    /// Disassembles bank 0 or 1 of the cartridge image instead, without running it
+    #[arg(long, value_name = "BANK", value_parser = clap::value_parser!(u8).range(0..=1), conflicts_with = "frames")]
+    bank: Option<u8>,
+
+    /// Offset in the bank, hexadecimal
+    #[arg(long, value_name = "OFFSET", value_parser = parse_offset, default_value = "0", requires = "bank")]
+    offset: usize,
+
+    /// First address, hexadecimal or label [default: PC, or where the bank data is shown at]
+    #[arg(long, value_name = "ADDR")]
+    start: Option<String>,
+
+    /// Number of instructions
+    #[arg(long, value_name = "COUNT", default_value_t = 64)]
+    count: usize,
+
+    /// Symbols file, cc65 .dbg or VICE labels
+    #[arg(long, value_name = "FILE")]
+    symbols: Option<PathBuf>,
+}
+
+fn parse_offset(s: &str) -> Result<usize, String> {
+    let digits = s
+        .strip_prefix('$')
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    usize::from_str_radix(digits, 16).map_err(|_| format!("Invalid offset '{s}'."))
+}
+
+fn load(args: &DisasmArgs) -> Result<Lynx, String> {
+    let mut lynx = load_lynx(args.rom.as_deref(), &args.cartridge, args.bank_size)?;
+    for _ in 0..args.frames {
+        run_frame(&mut lynx, |_| {});
+    }
+    Ok(lynx)
+}
+
+/// The data of a bank, and the address it is loaded at when the file tells,
+/// the program of `.o` files.
+fn image(args: &DisasmArgs, bank: u8) -> Result<(Vec<u8>, Option<u16>), String> {
+    let data = gamedb::read_cartridge(&args.cartridge, args.bank_size)
+        .map_err(|err| format!("Couldn't load Cartridge file: {err}."))?;
+    if let Some(header) = info::parse_bll(&data) {
+        if bank != 0 {
+            return Err(".o files only have bank 0.".to_string());
+        }
+        return Ok((data[BLL_HEADER_SIZE..].to_vec(), Some(header.load_address)));
+    }
+    let header = info::parse_lnx(&data).ok_or("Couldn't load Cartridge file.")?;
+    let bank0 = usize::from(header.bank0_page_size) * PAGES_PER_BANK;
+    let bank1 = usize::from(header.bank1_page_size) * PAGES_PER_BANK;
+    let range = match bank {
+        0 => 0..bank0,
+        _ => bank0..bank0 + bank1,
+    };
+    match data[LNX_HEADER_SIZE..].get(range) {
+        Some(data) if !data.is_empty() => Ok((data.to_vec(), None)),
+        _ => Err(format!("Bank {bank} is missing or truncated.")),
+    }
+}
+
+fn exit(err: &str) -> ! {
+    println!("Error: {err}");
+    std::process::exit(1);
+}
+
+fn address(start: &str, symbols: &Symbols) -> u16 {
+    parse_address(start, symbols).unwrap_or_else(|| exit(&format!("Unknown address '{start}'.")))
+}
+
+/// Runs the cartridge headless for a few frames, then disassembles the
+/// address space as the CPU sees it. With `--bank`, disassembles the
+/// cartridge image instead, shown at `--start`, by default at the offset
+/// past the load address of `.o` files.
+pub(crate) fn run(args: &DisasmArgs) {
+    let symbols = match &args.symbols {
+        None => Symbols::default(),
+        Some(path) => Symbols::load(path).unwrap_or_else(|err| exit(&err)),
+    };
+
+    let lines = match args.bank {
+        None => {
+            let lynx = load(args).unwrap_or_else(|err| exit(&err));
+            let start = match &args.start {
+                None => Registers::read(&lynx).pc,
+                Some(start) => address(start, &symbols),
+            };
+            disassemble_range(start, args.count, &|a| cpu::peek(&lynx, a), &symbols)
+        }
+        Some(bank) => {
+            let (data, load_address) = image(args, bank).unwrap_or_else(|err| exit(&err));
+            if args.offset >= data.len() {
+                exit(&format!(
+                    "Offset {:X} is past the end of the data.",
+                    args.offset
+                ));
+            }
+            let start = match &args.start {
+                None => load_address
+                    .unwrap_or(0)
+                    .wrapping_add(u16::try_from(args.offset & 0xFFFF).unwrap_or_default()),
+                Some(start) => address(start, &symbols),
+            };
+            let peek = |a: u16| {
+                data.get(args.offset + usize::from(a.wrapping_sub(start)))
+                    .copied()
+                    .unwrap_or(0)
+            };
+            disassemble_range(start, args.count, &peek, &symbols)
+        }
+    };
+    for line in lines {
+        println!("{line}");
+    }
+}
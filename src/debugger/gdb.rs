@@ -0,0 +1,382 @@
+use super::{cpu::Registers, opcodes::Access, DebugCommand, DebugEvent, Register, StopReason};
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+use thread_priority::{ThreadBuilderExt, ThreadPriority};
+
+const INTERRUPT: u8 = 0x03;
+
+/// Register layout of the `g`/`G` packets, described to GDB by this target
+/// description: A, X, Y, P, S as bytes then PC as a little endian word.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.holani.65c02">
+    <reg name="a" bitsize="8" type="uint8"/>
+    <reg name="x" bitsize="8" type="uint8"/>
+    <reg name="y" bitsize="8" type="uint8"/>
+    <reg name="p" bitsize="8" type="uint8"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>"#;
+
+const REGISTERS: [Register; 6] = [
+    Register::A,
+    Register::X,
+    Register::Y,
+    Register::P,
+    Register::S,
+    Register::Pc,
+];
+
+pub(crate) fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub(crate) fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_hex(s: &str) -> Option<u16> {
+    u16::from_str_radix(s, 16).ok()
+}
+
+fn register_bytes(registers: &Registers) -> Vec<u8> {
+    let [pc_lo, pc_hi] = registers.pc.to_le_bytes();
+    vec![
+        registers.a,
+        registers.x,
+        registers.y,
+        registers.p,
+        registers.s,
+        pc_lo,
+        pc_hi,
+    ]
+}
+
+fn stop_reply(reason: StopReason) -> String {
+    match reason {
+        StopReason::Watchpoint(addr, Access::Write) => format!("T05watch:{addr:x};"),
+        StopReason::Watchpoint(addr, Access::Read) => format!("T05rwatch:{addr:x};"),
+        StopReason::Watchpoint(addr, _) => format!("T05awatch:{addr:x};"),
+        StopReason::Pause => "T02".to_string(),
+        _ => "T05".to_string(),
+    }
+}
+
+/// One GDB connection: decodes the remote serial protocol packets and turns
+/// them into debugger commands.
+struct Session {
+    stream: TcpStream,
+    command_tx: kanal::Sender<DebugCommand>,
+    event_rx: kanal::Receiver<DebugEvent>,
+    buffer: Vec<u8>,
+    ack: bool,
+    running: bool,
+    last_stop: StopReason,
+}
+
+impl Session {
+    fn request<T>(
+        &self,
+        command: DebugCommand,
+        mut reply: impl FnMut(DebugEvent) -> Option<T>,
+    ) -> Option<T> {
+        self.command_tx.send(command).ok()?;
+        loop {
+            if let Some(value) = reply(self.event_rx.recv().ok()?) {
+                return Some(value);
+            }
+        }
+    }
+
+    fn registers(&self) -> Option<Registers> {
+        self.request(DebugCommand::Registers, |e| match e {
+            DebugEvent::Registers(r) => Some(r),
+            _ => None,
+        })
+    }
+
+    fn set_register(&self, register: Register, value: u16) -> Option<()> {
+        self.request(DebugCommand::SetRegister(register, value), |e| {
+            matches!(e, DebugEvent::Registers(_)).then_some(())
+        })
+    }
+
+    fn acknowledged(&self, command: DebugCommand) -> Option<()> {
+        self.request(command, |e| {
+            matches!(e, DebugEvent::Message(_)).then_some(())
+        })
+    }
+
+    fn wait_stop(&mut self) -> Option<String> {
+        loop {
+            if let DebugEvent::Stopped { reason, .. } = self.event_rx.recv().ok()? {
+                self.running = false;
+                self.last_stop = reason;
+                return Some(stop_reply(reason));
+            }
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) -> std::io::Result<()> {
+        let checksum = data.bytes().fold(0u8, u8::wrapping_add);
+        self.stream
+            .write_all(format!("${data}#{checksum:02x}").as_bytes())
+    }
+
+    /// Returns the next packet, `Some("\x03")` for an interrupt, `None` when
+    /// nothing complete has been received yet.
+    fn next_packet(&mut self) -> std::io::Result<Option<String>> {
+        loop {
+            if let Some(start) = self
+                .buffer
+                .iter()
+                .position(|b| *b == b'$' || *b == INTERRUPT)
+            {
+                if self.buffer[start] == INTERRUPT {
+                    self.buffer.drain(..=start);
+                    return Ok(Some("\x03".to_string()));
+                }
+                if let Some(end) = self.buffer[start..].iter().position(|b| *b == b'#') {
+                    let end = start + end;
+                    if self.buffer.len() >= end + 3 {
+                        let packet =
+                            String::from_utf8_lossy(&self.buffer[start + 1..end]).to_string();
+                        self.buffer.drain(..end + 3);
+                        if self.ack {
+                            self.stream.write_all(b"+")?;
+                        }
+                        return Ok(Some(packet));
+                    }
+                }
+            }
+
+            let mut data = [0; 1024];
+            match self.stream.read(&mut data) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(len) => self.buffer.extend_from_slice(&data[..len]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn watch(&self, kind: &str, addr: u16, len: u16, insert: bool) -> Option<()> {
+        let access = match kind {
+            "2" => Access::Write,
+            "3" => Access::Read,
+            _ => Access::ReadWrite,
+        };
+        for offset in 0..len.max(1) {
+            let addr = addr.wrapping_add(offset);
+            if insert {
+                self.acknowledged(DebugCommand::Watch(addr, access))?;
+            } else {
+                self.acknowledged(DebugCommand::Unwatch(addr))?;
+            }
+        }
+        Some(())
+    }
+
+    /// Handles one packet, returning the reply or `None` if it is malformed
+    /// or the reply comes later.
+    fn handle(&mut self, packet: &str) -> Option<String> {
+        let (command, args) = packet.split_at(packet.len().min(1));
+        Some(match command {
+            "?" => stop_reply(self.last_stop),
+            "g" => to_hex(&register_bytes(&self.registers()?)),
+            "G" => {
+                let bytes = from_hex(args)?;
+                for (register, value) in REGISTERS[..5].iter().zip(bytes.iter()) {
+                    self.set_register(*register, u16::from(*value))?;
+                }
+                if bytes.len() >= 7 {
+                    self.set_register(Register::Pc, u16::from_le_bytes([bytes[5], bytes[6]]))?;
+                }
+                "OK".to_string()
+            }
+            "p" => {
+                let bytes = register_bytes(&self.registers()?);
+                match parse_hex(args)? {
+                    5 => to_hex(&bytes[5..7]),
+                    n if n < 5 => to_hex(&bytes[usize::from(n)..=usize::from(n)]),
+                    _ => "E01".to_string(),
+                }
+            }
+            "P" => {
+                let (n, value) = args.split_once('=')?;
+                let bytes = from_hex(value)?;
+                let value =
+                    u16::from_le_bytes([*bytes.first()?, bytes.get(1).copied().unwrap_or(0)]);
+                match REGISTERS.get(usize::from(parse_hex(n)?)) {
+                    Some(register) => {
+                        self.set_register(*register, value)?;
+                        "OK".to_string()
+                    }
+                    None => "E01".to_string(),
+                }
+            }
+            "m" => {
+                let (addr, len) = args.split_once(',')?;
+                let (addr, len) = (parse_hex(addr)?, parse_hex(len)?);
+                let data = self.request(DebugCommand::Memory(addr, len), |e| match e {
+                    DebugEvent::Memory(_, data) => Some(data),
+                    _ => None,
+                })?;
+                to_hex(&data)
+            }
+            "M" => {
+                let (location, data) = args.split_once(':')?;
+                let (addr, _) = location.split_once(',')?;
+                self.acknowledged(DebugCommand::Poke(parse_hex(addr)?, from_hex(data)?))?;
+                "OK".to_string()
+            }
+            "Z" | "z" => {
+                let mut fields = args.split(',');
+                let kind = fields.next()?;
+                let addr = parse_hex(fields.next()?)?;
+                let len = parse_hex(fields.next().unwrap_or("1"))?;
+                match (kind, command) {
+                    ("0" | "1", "Z") => self.acknowledged(DebugCommand::Break(addr))?,
+                    ("0" | "1", _) => self.acknowledged(DebugCommand::Delete(addr))?,
+                    ("2" | "3" | "4", _) => self.watch(kind, addr, len, command == "Z")?,
+                    _ => return Some(String::new()),
+                }
+                "OK".to_string()
+            }
+            "c" | "s" => {
+                if !args.is_empty() {
+                    self.set_register(Register::Pc, parse_hex(args)?)?;
+                }
+                self.running = true;
+                if command == "c" {
+                    self.request(DebugCommand::Continue, |e| {
+                        matches!(e, DebugEvent::Resumed).then_some(())
+                    })?;
+                    return None;
+                }
+                self.command_tx.send(DebugCommand::Step(1)).ok()?;
+                self.wait_stop()?
+            }
+            "D" => {
+                let _ = self.command_tx.send(DebugCommand::Continue);
+                "OK".to_string()
+            }
+            "H" => "OK".to_string(),
+            "q" if args.starts_with("Supported") => {
+                "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+".to_string()
+            }
+            "q" if args.starts_with("Xfer:features:read:target.xml:") => {
+                let range = args.trim_start_matches("Xfer:features:read:target.xml:");
+                let (offset, len) = range.split_once(',')?;
+                let offset = usize::from(parse_hex(offset)?).min(TARGET_XML.len());
+                let end = (offset + usize::from(parse_hex(len)?)).min(TARGET_XML.len());
+                let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+                format!("{marker}{}", &TARGET_XML[offset..end])
+            }
+            "q" if args == "Attached" => "1".to_string(),
+            "q" if args == "C" => "QC1".to_string(),
+            "q" if args == "fThreadInfo" => "m1".to_string(),
+            "q" if args == "sThreadInfo" => "l".to_string(),
+            "Q" if args == "StartNoAckMode" => {
+                self.ack = false;
+                "OK".to_string()
+            }
+            _ => String::new(),
+        })
+    }
+
+    fn serve(&mut self) -> std::io::Result<()> {
+        // GDB expects the target halted when it attaches. The debugger answers
+        // with a stop even when it is stopped already.
+        let _ = self.command_tx.send(DebugCommand::Pause);
+        let _ = self.wait_stop();
+
+        loop {
+            if self.running {
+                if let Ok(Some(DebugEvent::Stopped { reason, .. })) = self.event_rx.try_recv() {
+                    self.running = false;
+                    self.last_stop = reason;
+                    self.send_packet(&stop_reply(reason))?;
+                }
+            }
+
+            let Some(packet) = self.next_packet()? else {
+                continue;
+            };
+            if packet == "\x03" {
+                let _ = self.command_tx.send(DebugCommand::Pause);
+                continue;
+            }
+            if packet == "k" {
+                let _ = self.command_tx.send(DebugCommand::Continue);
+                return Ok(());
+            }
+            match self.handle(&packet) {
+                Some(reply) => {
+                    self.send_packet(&reply)?;
+                    if packet.starts_with('D') {
+                        return Ok(());
+                    }
+                }
+                // Malformed packet, a continue replies when the target stops.
+                None if !self.running => self.send_packet("E01")?,
+                None => {}
+            }
+        }
+    }
+}
+
+/// Spawns the GDB remote serial protocol server, one client at a time.
+pub(crate) fn spawn(port: u16) -> (kanal::Receiver<DebugCommand>, kanal::Sender<DebugEvent>) {
+    let (command_tx, command_rx) = kanal::unbounded::<DebugCommand>();
+    let (event_tx, event_rx) = kanal::unbounded::<DebugEvent>();
+
+    std::thread::Builder::new()
+        .name("GdbStub".to_string())
+        .spawn_with_priority(ThreadPriority::Min, move |_| {
+            let listener = TcpListener::bind(format!("127.0.0.1:{port}")).unwrap();
+            println!("GDB server listening on 127.0.0.1:{port}");
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                stream
+                    .set_read_timeout(Some(Duration::from_millis(20)))
+                    .unwrap();
+                stream.set_nodelay(true).unwrap();
+                println!("GDB client connected.");
+                // Drop what was emitted while nobody was listening.
+                while let Ok(Some(_)) = event_rx.try_recv() {}
+                let mut session = Session {
+                    stream,
+                    command_tx: command_tx.clone(),
+                    event_rx: event_rx.clone(),
+                    buffer: vec![],
+                    ack: true,
+                    running: false,
+                    last_stop: StopReason::Pause,
+                };
+                if let Err(e) = session.serve() {
+                    println!("GDB client disconnected: {e}.");
+                    // Don't leave the emulation stopped without a client.
+                    let _ = command_tx.send(DebugCommand::Continue);
+                }
+            }
+        })
+        .expect("Could not create the GDB server thread.");
+
+    (command_rx, event_tx)
+}
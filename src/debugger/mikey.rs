@@ -0,0 +1,161 @@
+//! Mikey timers, audio channels and interrupts, decoded from its registers.
+
+pub(crate) const MIKEY_BASE: u16 = 0xFD00;
+const TIMERS: usize = 0x00;
+const AUDIO: usize = 0x20;
+const ATTENUATION: usize = 0x40;
+const MPAN: usize = 0x44;
+const MSTEREO: usize = 0x50;
+const INTSET: usize = 0x81;
+
+const CLOCKS: [&str; 8] = ["1us", "2us", "4us", "8us", "16us", "32us", "64us", "linked"];
+
+/// Timer each counter is clocked by when its clock select is "linked",
+/// timers are 0-7 and audio channels 8-11.
+const LINKED_TO: [Option<usize>; 12] = [
+    None,
+    Some(11),
+    Some(0),
+    Some(1),
+    Some(2),
+    Some(3),
+    None,
+    Some(5),
+    Some(7),
+    Some(8),
+    Some(9),
+    Some(10),
+];
+
+/// Name of a timer (0-7) or audio channel (8-11).
+pub(crate) fn counter_name(counter: usize) -> String {
+    if counter < 8 {
+        format!("T{counter}")
+    } else {
+        format!("A{}", counter - 8)
+    }
+}
+
+/// Control and status bits shared by timers and audio channels.
+pub(crate) struct Counter {
+    pub backup: u8,
+    pub count: u8,
+    pub control: u8,
+    pub status: u8,
+}
+
+impl Counter {
+    pub(crate) fn enabled(&self) -> bool {
+        self.control & 0x08 != 0
+    }
+
+    pub(crate) fn reload(&self) -> bool {
+        self.control & 0x10 != 0
+    }
+
+    pub(crate) fn done(&self) -> bool {
+        self.status & 0x08 != 0
+    }
+
+    pub(crate) fn clock(&self) -> &'static str {
+        CLOCKS[usize::from(self.control & 7)]
+    }
+
+    fn linked(&self) -> bool {
+        self.control & 7 == 7
+    }
+}
+
+pub(crate) struct Timer {
+    pub counter: Counter,
+    /// Counter clocking this one, when linked.
+    pub source: Option<usize>,
+}
+
+impl Timer {
+    pub(crate) fn interrupt_enabled(&self) -> bool {
+        self.counter.control & 0x80 != 0
+    }
+}
+
+pub(crate) struct AudioChannel {
+    pub counter: Counter,
+    pub source: Option<usize>,
+    pub volume: i8,
+    pub output: i8,
+    /// 12 bit shift register.
+    pub shift: u16,
+    /// 12 bits, set for the shift register bits fed back.
+    pub taps: u16,
+    pub integrate: bool,
+    pub attenuation: u8,
+}
+
+pub(crate) struct Mikey {
+    pub timers: Vec<Timer>,
+    pub audio: Vec<AudioChannel>,
+    pub pending: u8,
+    pub panning: u8,
+    pub stereo: u8,
+}
+
+impl Mikey {
+    /// Decodes the 256 bytes read from `MIKEY_BASE`.
+    pub(crate) fn decode(registers: &[u8]) -> Self {
+        let source = |counter: usize, linked: bool| LINKED_TO[counter].filter(|_| linked);
+
+        let timers = (0..8)
+            .map(|i| {
+                let base = TIMERS + i * 4;
+                let counter = Counter {
+                    backup: registers[base],
+                    control: registers[base + 1],
+                    count: registers[base + 2],
+                    status: registers[base + 3],
+                };
+                Timer {
+                    source: source(i, counter.linked()),
+                    counter,
+                }
+            })
+            .collect();
+
+        let audio = (0..4)
+            .map(|i| {
+                let base = AUDIO + i * 8;
+                let feedback = registers[base + 1];
+                let control = registers[base + 5];
+                let other = registers[base + 7];
+                let counter = Counter {
+                    backup: registers[base + 4],
+                    control,
+                    count: registers[base + 6],
+                    status: other & 0x0F,
+                };
+                // SHFTFB holds the taps of bits 11, 10 and 5 to 0, the control
+                // register the one of bit 7.
+                let taps = (u16::from(feedback & 0xC0) << 4)
+                    | u16::from(feedback & 0x3F)
+                    | u16::from(control & 0x80);
+                AudioChannel {
+                    source: source(8 + i, counter.linked()),
+                    counter,
+                    volume: i8::from_le_bytes([registers[base]]),
+                    output: i8::from_le_bytes([registers[base + 2]]),
+                    shift: (u16::from(other >> 4) << 8) | u16::from(registers[base + 3]),
+                    taps,
+                    integrate: control & 0x20 != 0,
+                    attenuation: registers[ATTENUATION + i],
+                }
+            })
+            .collect();
+
+        Self {
+            timers,
+            audio,
+            pending: registers[INTSET],
+            panning: registers[MPAN],
+            stereo: registers[MSTEREO],
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use crate::runner::{RunnerCommand, RunnerHooks};
+use cpu::Registers;
+use holani::lynx::Lynx;
+use opcodes::{disassemble, disassemble_range, effective_address, Access, Opcode};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
+use symbols::Symbols;
+
+pub(crate) mod cpu;
+pub(crate) mod disasm;
+pub(crate) mod gdb;
+pub(crate) mod mikey;
+pub(crate) mod opcodes;
+pub(crate) mod repl;
+pub(crate) mod suzy;
+pub(crate) mod symbols;
+pub(crate) mod trace;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Register {
+    Pc,
+    A,
+    X,
+    Y,
+    S,
+    P,
+}
+
+pub(crate) enum DebugCommand {
+    Continue,
+    Pause,
+    Step(u32),
+    Next,
+    Finish,
+    Break(u16),
+    Delete(u16),
+    Watch(u16, Access),
+    Unwatch(u16),
+    List,
+    Registers,
+    Memory(u16, u16),
+    SetRegister(Register, u16),
+    Poke(u16, Vec<u8>),
+    /// Disassembles instructions from an address, PC by default.
+    Disassemble(Option<u16>, u16),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StopReason {
+    Step,
+    Pause,
+    Breakpoint,
+    Watchpoint(u16, Access),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Step => write!(f, "step"),
+            StopReason::Pause => write!(f, "paused"),
+            StopReason::Breakpoint => write!(f, "breakpoint"),
+            StopReason::Watchpoint(addr, Access::Write) => {
+                write!(f, "watchpoint, write ${addr:04X}")
+            }
+            StopReason::Watchpoint(addr, Access::ReadWrite) => {
+                write!(f, "watchpoint, read/write ${addr:04X}")
+            }
+            StopReason::Watchpoint(addr, _) => write!(f, "watchpoint, read ${addr:04X}"),
+        }
+    }
+}
+
+pub(crate) enum DebugEvent {
+    Stopped {
+        reason: StopReason,
+        registers: Registers,
+        instruction: String,
+    },
+    Resumed,
+    Registers(Registers),
+    Memory(u16, Vec<u8>),
+    Message(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RunState {
+    Running,
+    Paused,
+    Pausing,
+    Stepping(u32),
+    StepOver(u16),
+    StepOut(u8),
+}
+
+/// Debugger living in the runner thread.
+///
+/// It is checked after every tick and acts when the CPU is about to execute a
+/// new instruction: it stops on breakpoints, on watchpoints matching the
+/// effective address of that instruction, or when a step completes. While
+/// stopped the runner thread blocks in `wait`, the emulation window keeps
+/// showing the last frame and the runner commands are still carried out.
+pub(crate) struct Debugger {
+    command_rx: kanal::Receiver<DebugCommand>,
+    event_tx: kanal::Sender<DebugEvent>,
+    symbols: Arc<Symbols>,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: BTreeMap<u16, Access>,
+    state: RunState,
+    pc: Option<u16>,
+    opcode: u8,
+}
+
+impl Debugger {
+    pub(crate) fn new(
+        command_rx: kanal::Receiver<DebugCommand>,
+        event_tx: kanal::Sender<DebugEvent>,
+        symbols: Arc<Symbols>,
+    ) -> Self {
+        Self {
+            command_rx,
+            event_tx,
+            symbols,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            state: RunState::Running,
+            pc: None,
+            opcode: 0,
+        }
+    }
+
+    /// Returns true when the emulation must stop before the instruction at PC.
+    pub(crate) fn check(&mut self, lynx: &Lynx) -> bool {
+        let registers = Registers::read(lynx);
+        if self.pc == Some(registers.pc) {
+            return false;
+        }
+        self.pc = Some(registers.pc);
+        let previous_opcode = self.opcode;
+        self.opcode = cpu::peek(lynx, registers.pc);
+
+        let reason = match self.state {
+            RunState::Paused | RunState::Running => None,
+            RunState::Pausing => Some(StopReason::Pause),
+            RunState::Stepping(1) => Some(StopReason::Step),
+            RunState::Stepping(n) => {
+                self.state = RunState::Stepping(n - 1);
+                None
+            }
+            RunState::StepOver(ret) => (registers.pc == ret).then_some(StopReason::Step),
+            RunState::StepOut(s) => {
+                let returned = matches!(Opcode::decode(previous_opcode).mnemonic, "RTS" | "RTI");
+                (returned && registers.s > s).then_some(StopReason::Step)
+            }
+        };
+        let reason = reason
+            .or_else(|| {
+                self.breakpoints
+                    .contains(&registers.pc)
+                    .then_some(StopReason::Breakpoint)
+            })
+            .or_else(|| self.watchpoint_hit(lynx, &registers));
+
+        match reason {
+            None => false,
+            Some(reason) => {
+                self.stop(lynx, reason);
+                true
+            }
+        }
+    }
+
+    /// Handles the commands received while running.
+    pub(crate) fn poll(&mut self, lynx: &mut Lynx) {
+        while let Ok(Some(command)) = self.command_rx.try_recv() {
+            self.handle(command, lynx);
+        }
+    }
+
+    /// Handles the commands received while the runner is paused. The CPU
+    /// doesn't move then, so a pause request stops right away. Returns true
+    /// when stopped.
+    pub(crate) fn poll_paused(&mut self, lynx: &mut Lynx) -> bool {
+        self.poll(lynx);
+        if self.state == RunState::Pausing {
+            self.stop(lynx, StopReason::Pause);
+        }
+        self.state == RunState::Paused
+    }
+
+    /// Blocks while stopped, handling the debugger and the runner commands.
+    /// Returns true if the emulation should terminate.
+    pub(crate) fn wait(
+        &mut self,
+        lynx: &mut Lynx,
+        input_rx: &kanal::Receiver<(u8, u8)>,
+        runner_rx: &kanal::Receiver<RunnerCommand>,
+        hooks: &mut RunnerHooks,
+    ) -> bool {
+        while self.state == RunState::Paused {
+            if input_rx.is_disconnected() || self.command_rx.is_disconnected() {
+                return true;
+            }
+            while let Ok(Some(command)) = runner_rx.try_recv() {
+                command.execute(lynx, hooks);
+            }
+            if let Ok(command) = self.command_rx.recv_timeout(Duration::from_millis(10)) {
+                self.handle(command, lynx);
+            }
+        }
+        false
+    }
+
+    fn watchpoint_hit(&self, lynx: &Lynx, registers: &Registers) -> Option<StopReason> {
+        if self.watchpoints.is_empty() {
+            return None;
+        }
+        let addr = effective_address(registers.pc, registers.x, registers.y, &|a| {
+            cpu::peek(lynx, a)
+        })?;
+        let access = Opcode::decode(self.opcode).access();
+        match self.watchpoints.get(&addr) {
+            Some(Access::ReadWrite) => Some(StopReason::Watchpoint(addr, access)),
+            Some(watched) if access == Access::ReadWrite || access == *watched => {
+                Some(StopReason::Watchpoint(addr, access))
+            }
+            _ => None,
+        }
+    }
+
+    fn stop(&mut self, lynx: &Lynx, reason: StopReason) {
+        self.state = RunState::Paused;
+        let registers = Registers::read(lynx);
+        let (mut instruction, _) =
+            disassemble(registers.pc, &|a| cpu::peek(lynx, a), &self.symbols);
+        if let Some(location) = self.symbols.describe(registers.pc) {
+            instruction = format!("{instruction}    <{location}>");
+        }
+        self.send(DebugEvent::Stopped {
+            reason,
+            registers,
+            instruction,
+        });
+    }
+
+    fn resume(&mut self, state: RunState) {
+        self.state = state;
+        if state == RunState::Running {
+            self.send(DebugEvent::Resumed);
+        }
+    }
+
+    fn handle(&mut self, command: DebugCommand, lynx: &mut Lynx) {
+        let registers = Registers::read(lynx);
+        match command {
+            DebugCommand::Continue => self.resume(RunState::Running),
+            // Already stopped, the stop is reported again for the front-ends
+            // that attach to a halted target.
+            DebugCommand::Pause if self.state == RunState::Paused => {
+                self.stop(lynx, StopReason::Pause);
+            }
+            DebugCommand::Pause => self.state = RunState::Pausing,
+            DebugCommand::Step(count) => self.resume(RunState::Stepping(count.max(1))),
+            DebugCommand::Next => {
+                if Opcode::decode(cpu::peek(lynx, registers.pc)).mnemonic == "JSR" {
+                    self.resume(RunState::StepOver(registers.pc.wrapping_add(3)));
+                } else {
+                    self.resume(RunState::Stepping(1));
+                }
+            }
+            DebugCommand::Finish => self.resume(RunState::StepOut(registers.s)),
+            DebugCommand::Break(addr) => {
+                self.breakpoints.insert(addr);
+                self.message(format!("Breakpoint set at {}.", self.location(addr)));
+            }
+            DebugCommand::Delete(addr) => {
+                if self.breakpoints.remove(&addr) {
+                    self.message(format!("Breakpoint at {} deleted.", self.location(addr)));
+                } else {
+                    self.message(format!("No breakpoint at {}.", self.location(addr)));
+                }
+            }
+            DebugCommand::Watch(addr, access) => {
+                self.watchpoints.insert(addr, access);
+                self.message(format!("Watchpoint set at {}.", self.location(addr)));
+            }
+            DebugCommand::Unwatch(addr) => {
+                if self.watchpoints.remove(&addr).is_some() {
+                    self.message(format!("Watchpoint at {} deleted.", self.location(addr)));
+                } else {
+                    self.message(format!("No watchpoint at {}.", self.location(addr)));
+                }
+            }
+            DebugCommand::List => {
+                let mut list: Vec<String> = self
+                    .breakpoints
+                    .iter()
+                    .map(|addr| format!("break {}", self.location(*addr)))
+                    .collect();
+                list.extend(self.watchpoints.iter().map(|(addr, access)| {
+                    let kind = match access {
+                        Access::Read => "r",
+                        Access::Write => "w",
+                        _ => "rw",
+                    };
+                    format!("watch {} {kind}", self.location(*addr))
+                }));
+                if list.is_empty() {
+                    list.push("No breakpoints nor watchpoints.".to_string());
+                }
+                self.message(list.join("\n"));
+            }
+            DebugCommand::Registers => self.send(DebugEvent::Registers(registers)),
+            DebugCommand::Memory(addr, len) => {
+                let data = (0..len)
+                    .map(|i| cpu::peek(lynx, addr.wrapping_add(i)))
+                    .collect();
+                self.send(DebugEvent::Memory(addr, data));
+            }
+            DebugCommand::SetRegister(register, value) => {
+                let mut registers = registers;
+                let byte = value.to_le_bytes()[0];
+                match register {
+                    Register::Pc => registers.pc = value,
+                    Register::A => registers.a = byte,
+                    Register::X => registers.x = byte,
+                    Register::Y => registers.y = byte,
+                    Register::S => registers.s = byte,
+                    Register::P => registers.p = byte,
+                }
+                registers.write(lynx);
+                self.pc = Some(registers.pc);
+                self.opcode = cpu::peek(lynx, registers.pc);
+                self.send(DebugEvent::Registers(registers));
+            }
+            DebugCommand::Poke(addr, data) => {
+                for (offset, byte) in (0u16..).zip(data.iter()) {
+                    cpu::poke(lynx, addr.wrapping_add(offset), *byte);
+                }
+                self.message(format!("{} byte(s) written at ${addr:04X}.", data.len()));
+            }
+            DebugCommand::Disassemble(addr, count) => {
+                let lines = disassemble_range(
+                    addr.unwrap_or(registers.pc),
+                    usize::from(count),
+                    &|a| cpu::peek(lynx, a),
+                    &self.symbols,
+                );
+                self.message(lines.join("\n"));
+            }
+        }
+    }
+
+    /// `$1234`, followed by its label if any.
+    fn location(&self, addr: u16) -> String {
+        match self.symbols.describe(addr) {
+            Some(name) => format!("${addr:04X} <{name}>"),
+            None => format!("${addr:04X}"),
+        }
+    }
+
+    fn message(&self, message: String) {
+        self.send(DebugEvent::Message(message));
+    }
+
+    fn send(&self, event: DebugEvent) {
+        let _ = self.event_tx.send(event);
+    }
+}
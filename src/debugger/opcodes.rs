@@ -0,0 +1,215 @@
+use super::symbols::Symbols;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    ZeroPageIndirect,
+    AbsoluteIndirectX,
+    Relative,
+}
+
+impl Mode {
+    pub(crate) fn len(self) -> u16 {
+        match self {
+            Mode::Implied | Mode::Accumulator => 1,
+            Mode::Immediate
+            | Mode::ZeroPage
+            | Mode::ZeroPageX
+            | Mode::ZeroPageY
+            | Mode::IndirectX
+            | Mode::IndirectY
+            | Mode::ZeroPageIndirect
+            | Mode::Relative => 2,
+            Mode::Absolute
+            | Mode::AbsoluteX
+            | Mode::AbsoluteY
+            | Mode::Indirect
+            | Mode::AbsoluteIndirectX => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Access {
+    None,
+    Read,
+    Write,
+    ReadWrite,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Opcode {
+    pub mnemonic: &'static str,
+    pub mode: Mode,
+}
+
+const fn op(mnemonic: &'static str, mode: Mode) -> Opcode {
+    Opcode { mnemonic, mode }
+}
+
+use Mode::{
+    Absolute as ABS, AbsoluteIndirectX as AIX, AbsoluteX as ABX, AbsoluteY as ABY,
+    Accumulator as ACC, Immediate as IMM, Implied as IMP, Indirect as IND, IndirectX as IZX,
+    IndirectY as IZY, Relative as REL, ZeroPage as ZP, ZeroPageIndirect as IZP, ZeroPageX as ZPX,
+    ZeroPageY as ZPY,
+};
+
+/// 65SC02 as found in the Lynx: the 65C02 set without the Rockwell bit
+/// instructions nor WAI/STP, those slots being single byte NOPs.
+#[rustfmt::skip]
+pub(crate) const OPCODES: [Opcode; 256] = [
+    op("BRK", IMP), op("ORA", IZX), op("NOP", IMM), op("NOP", IMP), op("TSB", ZP),  op("ORA", ZP),  op("ASL", ZP),  op("NOP", IMP),
+    op("PHP", IMP), op("ORA", IMM), op("ASL", ACC), op("NOP", IMP), op("TSB", ABS), op("ORA", ABS), op("ASL", ABS), op("NOP", IMP),
+    op("BPL", REL), op("ORA", IZY), op("ORA", IZP), op("NOP", IMP), op("TRB", ZP),  op("ORA", ZPX), op("ASL", ZPX), op("NOP", IMP),
+    op("CLC", IMP), op("ORA", ABY), op("INC", ACC), op("NOP", IMP), op("TRB", ABS), op("ORA", ABX), op("ASL", ABX), op("NOP", IMP),
+    op("JSR", ABS), op("AND", IZX), op("NOP", IMM), op("NOP", IMP), op("BIT", ZP),  op("AND", ZP),  op("ROL", ZP),  op("NOP", IMP),
+    op("PLP", IMP), op("AND", IMM), op("ROL", ACC), op("NOP", IMP), op("BIT", ABS), op("AND", ABS), op("ROL", ABS), op("NOP", IMP),
+    op("BMI", REL), op("AND", IZY), op("AND", IZP), op("NOP", IMP), op("BIT", ZPX), op("AND", ZPX), op("ROL", ZPX), op("NOP", IMP),
+    op("SEC", IMP), op("AND", ABY), op("DEC", ACC), op("NOP", IMP), op("BIT", ABX), op("AND", ABX), op("ROL", ABX), op("NOP", IMP),
+    op("RTI", IMP), op("EOR", IZX), op("NOP", IMM), op("NOP", IMP), op("NOP", ZP),  op("EOR", ZP),  op("LSR", ZP),  op("NOP", IMP),
+    op("PHA", IMP), op("EOR", IMM), op("LSR", ACC), op("NOP", IMP), op("JMP", ABS), op("EOR", ABS), op("LSR", ABS), op("NOP", IMP),
+    op("BVC", REL), op("EOR", IZY), op("EOR", IZP), op("NOP", IMP), op("NOP", ZPX), op("EOR", ZPX), op("LSR", ZPX), op("NOP", IMP),
+    op("CLI", IMP), op("EOR", ABY), op("PHY", IMP), op("NOP", IMP), op("NOP", ABS), op("EOR", ABX), op("LSR", ABX), op("NOP", IMP),
+    op("RTS", IMP), op("ADC", IZX), op("NOP", IMM), op("NOP", IMP), op("STZ", ZP),  op("ADC", ZP),  op("ROR", ZP),  op("NOP", IMP),
+    op("PLA", IMP), op("ADC", IMM), op("ROR", ACC), op("NOP", IMP), op("JMP", IND), op("ADC", ABS), op("ROR", ABS), op("NOP", IMP),
+    op("BVS", REL), op("ADC", IZY), op("ADC", IZP), op("NOP", IMP), op("STZ", ZPX), op("ADC", ZPX), op("ROR", ZPX), op("NOP", IMP),
+    op("SEI", IMP), op("ADC", ABY), op("PLY", IMP), op("NOP", IMP), op("JMP", AIX), op("ADC", ABX), op("ROR", ABX), op("NOP", IMP),
+    op("BRA", REL), op("STA", IZX), op("NOP", IMM), op("NOP", IMP), op("STY", ZP),  op("STA", ZP),  op("STX", ZP),  op("NOP", IMP),
+    op("DEY", IMP), op("BIT", IMM), op("TXA", IMP), op("NOP", IMP), op("STY", ABS), op("STA", ABS), op("STX", ABS), op("NOP", IMP),
+    op("BCC", REL), op("STA", IZY), op("STA", IZP), op("NOP", IMP), op("STY", ZPX), op("STA", ZPX), op("STX", ZPY), op("NOP", IMP),
+    op("TYA", IMP), op("STA", ABY), op("TXS", IMP), op("NOP", IMP), op("STZ", ABS), op("STA", ABX), op("STZ", ABX), op("NOP", IMP),
+    op("LDY", IMM), op("LDA", IZX), op("LDX", IMM), op("NOP", IMP), op("LDY", ZP),  op("LDA", ZP),  op("LDX", ZP),  op("NOP", IMP),
+    op("TAY", IMP), op("LDA", IMM), op("TAX", IMP), op("NOP", IMP), op("LDY", ABS), op("LDA", ABS), op("LDX", ABS), op("NOP", IMP),
+    op("BCS", REL), op("LDA", IZY), op("LDA", IZP), op("NOP", IMP), op("LDY", ZPX), op("LDA", ZPX), op("LDX", ZPY), op("NOP", IMP),
+    op("CLV", IMP), op("LDA", ABY), op("TSX", IMP), op("NOP", IMP), op("LDY", ABX), op("LDA", ABX), op("LDX", ABY), op("NOP", IMP),
+    op("CPY", IMM), op("CMP", IZX), op("NOP", IMM), op("NOP", IMP), op("CPY", ZP),  op("CMP", ZP),  op("DEC", ZP),  op("NOP", IMP),
+    op("INY", IMP), op("CMP", IMM), op("DEX", IMP), op("NOP", IMP), op("CPY", ABS), op("CMP", ABS), op("DEC", ABS), op("NOP", IMP),
+    op("BNE", REL), op("CMP", IZY), op("CMP", IZP), op("NOP", IMP), op("NOP", ZPX), op("CMP", ZPX), op("DEC", ZPX), op("NOP", IMP),
+    op("CLD", IMP), op("CMP", ABY), op("PHX", IMP), op("NOP", IMP), op("NOP", ABS), op("CMP", ABX), op("DEC", ABX), op("NOP", IMP),
+    op("CPX", IMM), op("SBC", IZX), op("NOP", IMM), op("NOP", IMP), op("CPX", ZP),  op("SBC", ZP),  op("INC", ZP),  op("NOP", IMP),
+    op("INX", IMP), op("SBC", IMM), op("NOP", IMP), op("NOP", IMP), op("CPX", ABS), op("SBC", ABS), op("INC", ABS), op("NOP", IMP),
+    op("BEQ", REL), op("SBC", IZY), op("SBC", IZP), op("NOP", IMP), op("NOP", ZPX), op("SBC", ZPX), op("INC", ZPX), op("NOP", IMP),
+    op("SED", IMP), op("SBC", ABY), op("PLX", IMP), op("NOP", IMP), op("NOP", ABS), op("SBC", ABX), op("INC", ABX), op("NOP", IMP),
+];
+
+impl Opcode {
+    pub(crate) fn decode(opcode: u8) -> Opcode {
+        OPCODES[opcode as usize]
+    }
+
+    /// How the instruction touches the memory at its effective address.
+    pub(crate) fn access(&self) -> Access {
+        if matches!(
+            self.mode,
+            Mode::Implied | Mode::Accumulator | Mode::Immediate | Mode::Relative
+        ) {
+            return Access::None;
+        }
+        match self.mnemonic {
+            "ORA" | "AND" | "EOR" | "ADC" | "SBC" | "CMP" | "CPX" | "CPY" | "BIT" | "LDA"
+            | "LDX" | "LDY" => Access::Read,
+            "STA" | "STX" | "STY" | "STZ" => Access::Write,
+            "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC" | "TSB" | "TRB" => Access::ReadWrite,
+            _ => Access::None,
+        }
+    }
+}
+
+/// Effective address of the data operand of the instruction at `pc`, given the
+/// current index registers. `None` for instructions without a memory operand.
+pub(crate) fn effective_address(pc: u16, x: u8, y: u8, peek: &impl Fn(u16) -> u8) -> Option<u16> {
+    let opcode = Opcode::decode(peek(pc));
+    if opcode.access() == Access::None {
+        return None;
+    }
+    let lo = peek(pc.wrapping_add(1));
+    let word = u16::from_le_bytes([lo, peek(pc.wrapping_add(2))]);
+    let zp_word =
+        |zp: u8| u16::from_le_bytes([peek(u16::from(zp)), peek(u16::from(zp.wrapping_add(1)))]);
+    Some(match opcode.mode {
+        Mode::ZeroPage => u16::from(lo),
+        Mode::ZeroPageX => u16::from(lo.wrapping_add(x)),
+        Mode::ZeroPageY => u16::from(lo.wrapping_add(y)),
+        Mode::Absolute => word,
+        Mode::AbsoluteX => word.wrapping_add(u16::from(x)),
+        Mode::AbsoluteY => word.wrapping_add(u16::from(y)),
+        Mode::IndirectX => zp_word(lo.wrapping_add(x)),
+        Mode::IndirectY => zp_word(lo).wrapping_add(u16::from(y)),
+        Mode::ZeroPageIndirect => zp_word(lo),
+        _ => return None,
+    })
+}
+
+/// Formats the instruction at `pc`, returning it with its length in bytes.
+/// Operand addresses with a label are shown by name.
+pub(crate) fn disassemble(pc: u16, peek: &impl Fn(u16) -> u8, symbols: &Symbols) -> (String, u16) {
+    let opcode = Opcode::decode(peek(pc));
+    let lo = peek(pc.wrapping_add(1));
+    let word = u16::from_le_bytes([lo, peek(pc.wrapping_add(2))]);
+    let zp = symbols
+        .name(u16::from(lo))
+        .map_or_else(|| format!("${lo:02X}"), str::to_string);
+    let abs = |addr: u16| {
+        symbols
+            .name(addr)
+            .map_or_else(|| format!("${addr:04X}"), str::to_string)
+    };
+    let operand = match opcode.mode {
+        Mode::Implied => String::new(),
+        Mode::Accumulator => " A".to_string(),
+        Mode::Immediate => format!(" #${lo:02X}"),
+        Mode::ZeroPage => format!(" {zp}"),
+        Mode::ZeroPageX => format!(" {zp},X"),
+        Mode::ZeroPageY => format!(" {zp},Y"),
+        Mode::Absolute => format!(" {}", abs(word)),
+        Mode::AbsoluteX => format!(" {},X", abs(word)),
+        Mode::AbsoluteY => format!(" {},Y", abs(word)),
+        Mode::Indirect => format!(" ({})", abs(word)),
+        Mode::IndirectX => format!(" ({zp},X)"),
+        Mode::IndirectY => format!(" ({zp}),Y"),
+        Mode::ZeroPageIndirect => format!(" ({zp})"),
+        Mode::AbsoluteIndirectX => format!(" ({},X)", abs(word)),
+        Mode::Relative => format!(" {}", abs(branch_target(pc, lo))),
+    };
+    (format!("{}{operand}", opcode.mnemonic), opcode.mode.len())
+}
+
+/// Disassembles `count` instructions from `pc`, one per line with the
+/// address, the instruction bytes and the labels.
+pub(crate) fn disassemble_range(
+    pc: u16,
+    count: usize,
+    peek: &impl Fn(u16) -> u8,
+    symbols: &Symbols,
+) -> Vec<String> {
+    let mut lines = vec![];
+    let mut pc = pc;
+    for _ in 0..count {
+        if let Some(name) = symbols.name(pc) {
+            lines.push(format!("{name}:"));
+        }
+        let (instruction, len) = disassemble(pc, peek, symbols);
+        let bytes: Vec<String> = (0..len)
+            .map(|i| format!("{:02X}", peek(pc.wrapping_add(i))))
+            .collect();
+        lines.push(format!("${pc:04X}  {:<8}  {instruction}", bytes.join(" ")));
+        pc = pc.wrapping_add(len);
+    }
+    lines
+}
+
+pub(crate) fn branch_target(pc: u16, offset: u8) -> u16 {
+    pc.wrapping_add(2)
+        .wrapping_add_signed(i16::from(offset as i8))
+}
@@ -0,0 +1,201 @@
+use super::{opcodes::Access, symbols::Symbols, DebugCommand, DebugEvent, Register};
+use std::{
+    io::{BufRead, Write},
+    sync::Arc,
+};
+use thread_priority::{ThreadBuilderExt, ThreadPriority};
+
+const HELP: &str = "\
+Addresses, values and counts are hexadecimal, addresses can be labels
+that don't read as a number:
+  c, continue            resume execution
+  p, pause               stop at the next instruction
+  s, step [count]        execute one or <count> instruction(s)
+  n, next                step over subroutine calls
+  f, finish              run until the current subroutine returns
+  b, break <addr>        set a breakpoint
+  d, delete <addr>       delete a breakpoint
+  w, watch <addr> [r|w]  stop on reads and/or writes at <addr>
+  u, unwatch <addr>      delete a watchpoint
+  l, list                list breakpoints and watchpoints
+  r, regs                show registers and flags
+  x <addr> [len]         dump memory
+  set <reg> <value>      set pc, a, x, y, s or p
+  poke <addr> <byte>...  write memory
+  dis [addr] [count]     disassemble from PC or <addr>
+  h, help                this help";
+
+pub(crate) fn parse_number(s: &str) -> Option<u16> {
+    let digits = s
+        .strip_prefix('$')
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+/// A hexadecimal address, or a label. Numbers win, a label like `beef` can't
+/// hide the address.
+pub(crate) fn parse_address(s: &str, symbols: &Symbols) -> Option<u16> {
+    parse_number(s).or_else(|| symbols.addr(s))
+}
+
+fn parse_byte(s: &str) -> Option<u8> {
+    parse_number(s).and_then(|v| u8::try_from(v).ok())
+}
+
+fn parse(line: &str, symbols: &Symbols) -> Result<DebugCommand, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let args: Vec<&str> = words.collect();
+    let addr = |i: usize| {
+        args.get(i)
+            .and_then(|a| parse_address(a, symbols))
+            .ok_or("Expected an address.".to_string())
+    };
+    let count = |i: usize, default: u16| match args.get(i) {
+        Some(count) => parse_number(count).ok_or(format!("Invalid number '{count}'.")),
+        None => Ok(default),
+    };
+
+    Ok(match command {
+        "c" | "continue" => DebugCommand::Continue,
+        "p" | "pause" => DebugCommand::Pause,
+        "s" | "step" => DebugCommand::Step(u32::from(count(0, 1)?)),
+        "n" | "next" => DebugCommand::Next,
+        "f" | "finish" => DebugCommand::Finish,
+        "b" | "break" => DebugCommand::Break(addr(0)?),
+        "d" | "delete" => DebugCommand::Delete(addr(0)?),
+        "w" | "watch" => DebugCommand::Watch(
+            addr(0)?,
+            match args.get(1).copied() {
+                None | Some("rw") => Access::ReadWrite,
+                Some("r") => Access::Read,
+                Some("w") => Access::Write,
+                Some(_) => return Err("Expected r, w or rw.".to_string()),
+            },
+        ),
+        "u" | "unwatch" => DebugCommand::Unwatch(addr(0)?),
+        "l" | "list" => DebugCommand::List,
+        "r" | "regs" => DebugCommand::Registers,
+        "x" => DebugCommand::Memory(addr(0)?, count(1, 0x40)?),
+        "set" => {
+            let register = match args.first().copied() {
+                Some("pc") => Register::Pc,
+                Some("a") => Register::A,
+                Some("x") => Register::X,
+                Some("y") => Register::Y,
+                Some("s" | "sp") => Register::S,
+                Some("p") => Register::P,
+                _ => return Err("Expected pc, a, x, y, s or p.".to_string()),
+            };
+            let value = args.get(1).ok_or("Expected a value.".to_string())?;
+            DebugCommand::SetRegister(
+                register,
+                parse_number(value).ok_or(format!("Invalid number '{value}'."))?,
+            )
+        }
+        "poke" => DebugCommand::Poke(
+            addr(0)?,
+            args.iter()
+                .skip(1)
+                .map(|b| parse_byte(b).ok_or(format!("Invalid byte '{b}'.")))
+                .collect::<Result<Vec<u8>, String>>()?,
+        ),
+        "dis" => DebugCommand::Disassemble(
+            if args.is_empty() {
+                None
+            } else {
+                Some(addr(0)?)
+            },
+            count(1, 0x10)?,
+        ),
+        _ => return Err(HELP.to_string()),
+    })
+}
+
+pub(crate) fn format_event(event: &DebugEvent) -> String {
+    match event {
+        DebugEvent::Stopped {
+            reason,
+            registers,
+            instruction,
+        } => format!(
+            "Stopped ({reason}).\n{registers}\n${:04X}  {instruction}",
+            registers.pc
+        ),
+        DebugEvent::Resumed => "Running.".to_string(),
+        DebugEvent::Registers(registers) => registers.to_string(),
+        DebugEvent::Memory(addr, data) => (0u16..)
+            .step_by(16)
+            .zip(data.chunks(16))
+            .map(|(offset, line)| {
+                let hex: Vec<String> = line.iter().map(|b| format!("{b:02X}")).collect();
+                let ascii: String = line
+                    .iter()
+                    .map(|b| {
+                        if b.is_ascii_graphic() {
+                            *b as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                format!(
+                    "${:04X}  {:<47}  {ascii}",
+                    addr.wrapping_add(offset),
+                    hex.join(" ")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        DebugEvent::Message(message) => message.clone(),
+    }
+}
+
+/// Spawns the terminal front-end of the debugger, reading commands on stdin
+/// and printing the debugger events on stdout.
+pub(crate) fn spawn(
+    symbols: Arc<Symbols>,
+) -> (kanal::Receiver<DebugCommand>, kanal::Sender<DebugEvent>) {
+    let (command_tx, command_rx) = kanal::unbounded::<DebugCommand>();
+    let (event_tx, event_rx) = kanal::unbounded::<DebugEvent>();
+
+    std::thread::Builder::new()
+        .name("DebuggerOutput".to_string())
+        .spawn_with_priority(ThreadPriority::Min, move |_| {
+            while let Ok(event) = event_rx.recv() {
+                println!("{}", format_event(&event));
+            }
+        })
+        .expect("Could not create the debugger output thread.");
+
+    std::thread::Builder::new()
+        .name("DebuggerRepl".to_string())
+        .spawn_with_priority(ThreadPriority::Min, move |_| {
+            println!("Debugger ready, 'help' lists the commands.");
+            if !symbols.is_empty() {
+                println!("{} symbols loaded.", symbols.len());
+            }
+            let stdin = std::io::stdin();
+            for line in stdin.lock().lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match parse(&line, &symbols) {
+                    Ok(command) => {
+                        if command_tx.send(command).is_err() {
+                            return;
+                        }
+                    }
+                    Err(message) => println!("{message}"),
+                }
+                let _ = std::io::stdout().flush();
+            }
+        })
+        .expect("Could not create the debugger REPL thread.");
+
+    (command_rx, event_tx)
+}
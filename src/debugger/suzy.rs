@@ -0,0 +1,376 @@
+//! Sprite control blocks, read back from RAM the way Suzy walks them.
+
+use super::{
+    cpu::{self, Registers},
+    opcodes::{effective_address, Access, Opcode},
+};
+use holani::lynx::Lynx;
+use std::collections::{BTreeMap, BTreeSet};
+
+const SCBNEXT: u16 = 0xFC10;
+const COLLOFF: u16 = 0xFC24;
+const SPRGO: u16 = 0xFC91;
+const GREEN: u16 = 0xFDA0;
+const BLUERED: u16 = 0xFDB0;
+/// Guards against garbage chains and sprite data.
+const MAX_SCBS: usize = 512;
+const MAX_LINES: usize = 1024;
+
+const SPRITE_TYPES: [&str; 8] = [
+    "background shadow",
+    "background",
+    "boundary shadow",
+    "boundary",
+    "normal",
+    "non collidable",
+    "xor shadow",
+    "shadow",
+];
+
+#[derive(Clone)]
+pub(crate) struct Scb {
+    pub addr: u16,
+    pub sprctl0: u8,
+    pub sprctl1: u8,
+    pub sprcoll: u8,
+    pub data: u16,
+    pub x: i16,
+    pub y: i16,
+    /// 8.8 fixed point, 0x100 is 1:1.
+    pub hsize: u16,
+    pub vsize: u16,
+    /// Pen index of each pixel value.
+    pub palette: [u8; 16],
+}
+
+impl Scb {
+    pub(crate) fn bpp(&self) -> u8 {
+        (self.sprctl0 >> 6) + 1
+    }
+
+    pub(crate) fn kind(&self) -> &'static str {
+        SPRITE_TYPES[usize::from(self.sprctl0 & 7)]
+    }
+
+    pub(crate) fn skipped(&self) -> bool {
+        self.sprctl1 & 0x04 != 0
+    }
+
+    pub(crate) fn collides(&self) -> bool {
+        self.sprcoll & 0x20 == 0
+    }
+
+    pub(crate) fn collision_number(&self) -> u8 {
+        self.sprcoll & 0x0F
+    }
+
+    fn literal(&self) -> bool {
+        self.sprctl1 & 0x80 != 0
+    }
+
+    /// Decodes the sprite data, unscaled, returning its size and RGBA pixels
+    /// with pixel value 0 transparent.
+    pub(crate) fn render(
+        &self,
+        peek: &impl Fn(u16) -> u8,
+        colors: &[[u8; 4]; 16],
+    ) -> (u16, u16, Vec<u8>) {
+        let pixels = self.decode(peek);
+        let (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) = (
+            pixels.keys().map(|(x, _)| *x).min(),
+            pixels.keys().map(|(x, _)| *x).max(),
+            pixels.keys().map(|(_, y)| *y).min(),
+            pixels.keys().map(|(_, y)| *y).max(),
+        ) else {
+            return (0, 0, vec![]);
+        };
+        let width = u16::try_from(max_x - min_x + 1).unwrap_or(0);
+        let height = u16::try_from(max_y - min_y + 1).unwrap_or(0);
+        let mut rgba = vec![0; usize::from(width) * usize::from(height) * 4];
+        for ((x, y), pixel) in pixels {
+            if pixel == 0 {
+                continue;
+            }
+            let pen = self.palette[usize::from(pixel)];
+            let (Ok(x), Ok(y)) = (usize::try_from(x - min_x), usize::try_from(y - min_y)) else {
+                continue;
+            };
+            let offset = (y * usize::from(width) + x) * 4;
+            rgba[offset..offset + 4].copy_from_slice(&colors[usize::from(pen)]);
+        }
+        (width, height, rgba)
+    }
+
+    /// Value of each pixel around the sprite origin. Quadrants are drawn from
+    /// the origin outwards, left and up ones starting one pixel off it.
+    fn decode(&self, peek: &impl Fn(u16) -> u8) -> BTreeMap<(i32, i32), u8> {
+        let mut pixels = BTreeMap::new();
+        let bpp = self.bpp();
+        // Quadrants go down-right, up-right, up-left then down-left; the start
+        // one is given by the left (bit 0) and up (bit 1) drawing directions.
+        let mut quadrant = match self.sprctl1 & 3 {
+            0 => 0,
+            1 => 3,
+            2 => 1,
+            _ => 2,
+        };
+        let mut addr = self.data;
+        let mut line = 0;
+
+        for _ in 0..MAX_LINES {
+            let offset = peek(addr);
+            match offset {
+                0 => break,
+                1 => {
+                    quadrant = (quadrant + 1) & 3;
+                    line = 0;
+                    addr = addr.wrapping_add(1);
+                    continue;
+                }
+                _ => {}
+            }
+            let mut right = quadrant < 2;
+            let mut down = quadrant == 0 || quadrant == 3;
+            if self.sprctl0 & 0x20 != 0 {
+                right = !right;
+            }
+            if self.sprctl0 & 0x10 != 0 {
+                down = !down;
+            }
+            let y = if down { line } else { -line - 1 };
+            let mut bits = BitReader::new(addr.wrapping_add(1), offset - 1);
+            let mut x = 0;
+            let mut plot = |pixel: u8| {
+                let px = if right { x } else { -x - 1 };
+                pixels.insert((px, y), pixel);
+                x += 1;
+            };
+
+            if self.literal() {
+                while let Some(pixel) = bits.read(peek, bpp) {
+                    plot(pixel);
+                }
+            } else {
+                while let Some(literal) = bits.read(peek, 1) {
+                    let Some(count) = bits.read(peek, 4) else {
+                        break;
+                    };
+                    if literal == 0 && count == 0 {
+                        break;
+                    }
+                    if literal == 1 {
+                        for _ in 0..=count {
+                            let Some(pixel) = bits.read(peek, bpp) else {
+                                break;
+                            };
+                            plot(pixel);
+                        }
+                    } else {
+                        let Some(pixel) = bits.read(peek, bpp) else {
+                            break;
+                        };
+                        for _ in 0..=count {
+                            plot(pixel);
+                        }
+                    }
+                }
+            }
+
+            line += 1;
+            addr = addr.wrapping_add(u16::from(offset));
+        }
+        pixels
+    }
+}
+
+struct BitReader {
+    addr: u16,
+    remaining: u32,
+    byte: u8,
+    bits: u8,
+}
+
+impl BitReader {
+    fn new(addr: u16, len: u8) -> Self {
+        Self {
+            addr,
+            remaining: u32::from(len) * 8,
+            byte: 0,
+            bits: 0,
+        }
+    }
+
+    fn read(&mut self, peek: &impl Fn(u16) -> u8, count: u8) -> Option<u8> {
+        if self.remaining < u32::from(count) {
+            return None;
+        }
+        self.remaining -= u32::from(count);
+        let mut value = 0;
+        for _ in 0..count {
+            if self.bits == 0 {
+                self.byte = peek(self.addr);
+                self.addr = self.addr.wrapping_add(1);
+                self.bits = 8;
+            }
+            value = (value << 1) | (self.byte >> 7);
+            self.byte <<= 1;
+            self.bits -= 1;
+        }
+        Some(value)
+    }
+}
+
+/// Follows the SCB chain from `head`, carrying the sizes and palette over
+/// the blocks that don't reload them.
+pub(crate) fn walk_chain(peek: &impl Fn(u16) -> u8, head: u16) -> Vec<Scb> {
+    let word = |addr: u16| u16::from_le_bytes([peek(addr), peek(addr.wrapping_add(1))]);
+    let mut scbs = vec![];
+    let mut visited = BTreeSet::new();
+    let mut hsize = 0x100;
+    let mut vsize = 0x100;
+    let mut palette: [u8; 16] = std::array::from_fn(|i| u8::try_from(i).unwrap_or(0));
+    let mut addr = head;
+
+    while addr & 0xFF00 != 0 && scbs.len() < MAX_SCBS && visited.insert(addr) {
+        let sprctl1 = peek(addr.wrapping_add(1));
+        let mut field = addr.wrapping_add(11);
+        let reload = (sprctl1 >> 4) & 3;
+        if reload >= 1 {
+            hsize = word(field);
+            vsize = word(field.wrapping_add(2));
+            field = field.wrapping_add(4);
+        }
+        // Stretch and tilt only matter when drawing scaled.
+        field = field.wrapping_add(2 * u16::from(reload.saturating_sub(1)));
+        if sprctl1 & 0x08 == 0 {
+            for i in 0..8 {
+                let byte = peek(field.wrapping_add(i));
+                palette[usize::from(i) * 2] = byte >> 4;
+                palette[usize::from(i) * 2 + 1] = byte & 0x0F;
+            }
+        }
+
+        scbs.push(Scb {
+            addr,
+            sprctl0: peek(addr),
+            sprctl1,
+            sprcoll: peek(addr.wrapping_add(2)),
+            data: word(addr.wrapping_add(5)),
+            x: i16::from_le_bytes(word(addr.wrapping_add(7)).to_le_bytes()),
+            y: i16::from_le_bytes(word(addr.wrapping_add(9)).to_le_bytes()),
+            hsize,
+            vsize,
+            palette,
+        });
+        addr = word(addr.wrapping_add(3));
+    }
+    scbs
+}
+
+/// Current Mikey palette as RGBA.
+pub(crate) fn colors(lynx: &Lynx) -> [[u8; 4]; 16] {
+    std::array::from_fn(|pen| {
+        let pen = u16::try_from(pen).unwrap_or(0);
+        let green = cpu::peek(lynx, GREEN + pen) & 0x0F;
+        let bluered = cpu::peek(lynx, BLUERED + pen);
+        let expand = |v: u8| (v << 4) | v;
+        [
+            expand(bluered & 0x0F),
+            expand(green),
+            expand(bluered >> 4),
+            0xFF,
+        ]
+    })
+}
+
+pub(crate) struct Sprite {
+    pub scb: Scb,
+    pub width: u16,
+    pub height: u16,
+    pub rgba: Vec<u8>,
+}
+
+/// Sprite lists Suzy was started on during a frame.
+pub(crate) struct SpriteFrame {
+    pub collision_depth: u8,
+    pub sprites: Vec<Sprite>,
+}
+
+/// Collects the SCB chains of one frame. Suzy is started by writing SPRGO,
+/// the instructions are decoded like for the debugger watchpoints to catch
+/// that write and read the chain before it runs.
+pub(crate) struct SpriteCapture {
+    reply_tx: kanal::Sender<SpriteFrame>,
+    frame: SpriteFrame,
+    pc: Option<u16>,
+    started: bool,
+}
+
+impl SpriteCapture {
+    pub(crate) fn new(reply_tx: kanal::Sender<SpriteFrame>) -> Self {
+        Self {
+            reply_tx,
+            frame: SpriteFrame {
+                collision_depth: 0,
+                sprites: vec![],
+            },
+            pc: None,
+            started: false,
+        }
+    }
+
+    /// Called after every tick.
+    pub(crate) fn tick(&mut self, lynx: &Lynx) {
+        let registers = Registers::read(lynx);
+        if self.pc == Some(registers.pc) {
+            return;
+        }
+        self.pc = Some(registers.pc);
+
+        let peek = |addr| cpu::peek(lynx, addr);
+        let opcode = Opcode::decode(peek(registers.pc));
+        if opcode.access() != Access::Write
+            || effective_address(registers.pc, registers.x, registers.y, &peek) != Some(SPRGO)
+        {
+            return;
+        }
+        let value = match opcode.mnemonic {
+            "STA" => registers.a,
+            "STX" => registers.x,
+            "STY" => registers.y,
+            _ => 0,
+        };
+        if value & 1 == 0 {
+            return;
+        }
+
+        let colors = colors(lynx);
+        let head = u16::from_le_bytes([peek(SCBNEXT), peek(SCBNEXT + 1)]);
+        self.frame.collision_depth = peek(COLLOFF);
+        for scb in walk_chain(&peek, head) {
+            let (width, height, rgba) = scb.render(&peek, &colors);
+            self.frame.sprites.push(Sprite {
+                scb,
+                width,
+                height,
+                rgba,
+            });
+        }
+    }
+
+    /// Called when a frame has been displayed. The capture starts on the first
+    /// one; on the next one, what was captured is sent and true is returned.
+    pub(crate) fn frame(&mut self) -> bool {
+        if !self.started {
+            self.started = true;
+            self.frame.sprites.clear();
+            return false;
+        }
+        let frame = SpriteFrame {
+            collision_depth: self.frame.collision_depth,
+            sprites: std::mem::take(&mut self.frame.sprites),
+        };
+        let _ = self.reply_tx.try_send(frame);
+        true
+    }
+}
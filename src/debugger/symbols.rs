@@ -0,0 +1,113 @@
+//! Address labels loaded from the assembler or linker output.
+//!
+//! Supported files:
+//! - cc65 debug info (`ld65 --dbgfile`, `.dbg`): the `sym` lines of type `lab`.
+//! - VICE labels (`ld65 -Ln`, usually `.lbl`): `al C:1234 .name`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+/// Labels are only used as a base for addresses this close after them.
+const MAX_OFFSET: u16 = 0x100;
+
+#[derive(Default)]
+pub(crate) struct Symbols {
+    by_addr: BTreeMap<u16, String>,
+    by_name: HashMap<String, u16>,
+}
+
+impl Symbols {
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|err| format!("Couldn't read symbols file '{}': {err}.", path.display()))?;
+        let mut symbols = Symbols::default();
+        for line in data.lines() {
+            let line = line.trim();
+            let symbol = if line.starts_with("sym") {
+                parse_dbg(line)
+            } else if line.starts_with("al ") {
+                parse_vice(line)
+            } else {
+                continue;
+            };
+            if let Some((name, addr)) = symbol {
+                symbols.insert(name, addr);
+            }
+        }
+        if symbols.by_addr.is_empty() {
+            return Err(format!("No symbols found in '{}'.", path.display()));
+        }
+        Ok(symbols)
+    }
+
+    fn insert(&mut self, name: String, addr: u16) {
+        // cc65 local labels start with '@', keep the global ones when both exist.
+        let local = name.starts_with('@');
+        match self.by_addr.get(&addr) {
+            Some(existing) if local || !existing.starts_with('@') => {}
+            _ => {
+                self.by_addr.insert(addr, name.clone());
+            }
+        }
+        self.by_name.insert(name, addr);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.by_addr.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.by_addr.is_empty()
+    }
+
+    pub(crate) fn addr(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Label at exactly `addr`.
+    pub(crate) fn name(&self, addr: u16) -> Option<&str> {
+        self.by_addr.get(&addr).map(String::as_str)
+    }
+
+    /// `name` or `name+$offset` from the closest label before `addr`.
+    pub(crate) fn describe(&self, addr: u16) -> Option<String> {
+        let (base, name) = self.by_addr.range(..=addr).next_back()?;
+        match addr - base {
+            0 => Some(name.clone()),
+            offset if offset < MAX_OFFSET => Some(format!("{name}+${offset:X}")),
+            _ => None,
+        }
+    }
+}
+
+/// `sym id=1,name="main",addrsize=absolute,scope=0,def=5,val=0x200,seg=0,type=lab`
+fn parse_dbg(line: &str) -> Option<(String, u16)> {
+    let mut name = None;
+    let mut val = None;
+    let mut label = false;
+    for field in line.trim_start_matches("sym").trim().split(',') {
+        match field.split_once('=')? {
+            ("name", value) => name = Some(value.trim_matches('"').to_string()),
+            ("val", value) => {
+                val = u32::from_str_radix(value.trim_start_matches("0x"), 16)
+                    .ok()
+                    .and_then(|v| u16::try_from(v).ok());
+            }
+            ("type", value) => label = value == "lab",
+            _ => {}
+        }
+    }
+    label.then_some((name?, val?))
+}
+
+/// `al C:1234 .name` or `al 001234 .name`
+fn parse_vice(line: &str) -> Option<(String, u16)> {
+    let mut words = line.split_whitespace().skip(1);
+    let addr = words.next()?;
+    let addr = addr.rsplit(':').next()?;
+    let addr = u32::from_str_radix(addr, 16).ok()?;
+    let name = words.next()?.trim_start_matches('.');
+    Some((name.to_string(), u16::try_from(addr).ok()?))
+}
@@ -0,0 +1,137 @@
+use super::{cpu, cpu::Registers, opcodes::disassemble, repl::parse_number, symbols::Symbols};
+use holani::lynx::Lynx;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    ops::RangeInclusive,
+    path::PathBuf,
+    sync::Arc,
+};
+
+#[derive(Clone)]
+pub(crate) struct TraceConfig {
+    path: PathBuf,
+    addresses: RangeInclusive<u16>,
+    frames: RangeInclusive<u64>,
+}
+
+impl TraceConfig {
+    pub(crate) fn new(
+        path: PathBuf,
+        addresses: Option<RangeInclusive<u16>>,
+        frames: Option<RangeInclusive<u64>>,
+    ) -> Self {
+        Self {
+            path,
+            addresses: addresses.unwrap_or(0..=0xFFFF),
+            frames: frames.unwrap_or(0..=u64::MAX),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+/// Parses `<start>-<end>` hexadecimal addresses.
+pub(crate) fn parse_address_range(s: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = s.split_once('-').ok_or("Expected <start>-<end>.")?;
+    match (parse_number(start), parse_number(end)) {
+        (Some(start), Some(end)) if start <= end => Ok(start..=end),
+        _ => Err(format!("Invalid address range '{s}'.")),
+    }
+}
+
+/// Parses `<first>-<last>` frame numbers, `<first>-` runs until the end.
+pub(crate) fn parse_frame_range(s: &str) -> Result<RangeInclusive<u64>, String> {
+    let (first, last) = s.split_once('-').ok_or("Expected <first>-<last>.")?;
+    let first: u64 = first
+        .parse()
+        .map_err(|_| format!("Invalid frame '{first}'."))?;
+    let last: u64 = match last {
+        "" => u64::MAX,
+        last => last
+            .parse()
+            .map_err(|_| format!("Invalid frame '{last}'."))?,
+    };
+    if first > last {
+        return Err(format!("Invalid frame range '{s}'."));
+    }
+    Ok(first..=last)
+}
+
+/// Writes one line per executed instruction, before it runs:
+///
+/// `<frame> <cycles> <PC>  <bytes>  <disassembly>  A=.. X=.. Y=.. SP=.. P=..`
+///
+/// followed by the label of PC when there is one.
+///
+/// Like the debugger, it relies on the program counter moving to notice a new
+/// instruction.
+pub(crate) struct Tracer {
+    config: TraceConfig,
+    symbols: Arc<Symbols>,
+    writer: BufWriter<File>,
+    frame: u64,
+    cycles: u64,
+    pc: Option<u16>,
+}
+
+impl Tracer {
+    pub(crate) fn new(config: &TraceConfig, symbols: Arc<Symbols>) -> std::io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(config.path())?),
+            config: config.clone(),
+            symbols,
+            frame: 0,
+            cycles: 0,
+            pc: None,
+        })
+    }
+
+    /// Called after every tick.
+    pub(crate) fn tick(&mut self, lynx: &Lynx) {
+        self.cycles += 1;
+        let registers = Registers::read(lynx);
+        if self.pc == Some(registers.pc) {
+            return;
+        }
+        self.pc = Some(registers.pc);
+
+        if !self.config.frames.contains(&self.frame)
+            || !self.config.addresses.contains(&registers.pc)
+        {
+            return;
+        }
+
+        let peek = |addr| cpu::peek(lynx, addr);
+        let (instruction, len) = disassemble(registers.pc, &peek, &self.symbols);
+        let bytes: Vec<String> = (0..len)
+            .map(|i| format!("{:02X}", peek(registers.pc.wrapping_add(i))))
+            .collect();
+        let _ = writeln!(
+            self.writer,
+            "{} {} {:04X}  {:<8}  {:<16}  A={:02X} X={:02X} Y={:02X} SP={:02X} P={:02X}{}",
+            self.frame,
+            self.cycles,
+            registers.pc,
+            bytes.join(" "),
+            instruction,
+            registers.a,
+            registers.x,
+            registers.y,
+            registers.s,
+            registers.p,
+            self.symbols
+                .name(registers.pc)
+                .map_or_else(String::new, |name| format!("  {name}"))
+        );
+    }
+
+    /// Called when a frame has been displayed. Flushes the trace so it is
+    /// complete up to that frame, even if the emulator is killed.
+    pub(crate) fn frame(&mut self) {
+        self.frame += 1;
+        let _ = self.writer.flush();
+    }
+}
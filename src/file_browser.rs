@@ -0,0 +1,211 @@
+use crate::overlay::Overlay;
+use macroquad::prelude::*;
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F7;
+const FONT_SIZE: f32 = 16.;
+const LINE_HEIGHT: f32 = 16.;
+const MARGIN: f32 = 8.;
+const HELP: &str = "F7 close  Up/Down/PgUp/PgDn select  Enter open  Backspace parent";
+/// Extensions of the files the emulator loads as cartridges.
+const CARTRIDGE_EXTENSIONS: [&str; 3] = ["lnx", "o", "lyx"];
+
+pub(crate) fn is_cartridge(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            CARTRIDGE_EXTENSIONS
+                .iter()
+                .any(|e| ext.eq_ignore_ascii_case(e))
+        })
+}
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    dir: bool,
+}
+
+/// Keyboard driven browser to pick a cartridge to load. The chosen file is
+/// left in the shared slot for the emulation loop to pick up.
+pub(crate) struct FileBrowser {
+    chosen: Rc<RefCell<Option<PathBuf>>>,
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    visible: bool,
+    selected: usize,
+    top: usize,
+    message: String,
+}
+
+impl FileBrowser {
+    pub(crate) fn new(dir: PathBuf, chosen: Rc<RefCell<Option<PathBuf>>>) -> Self {
+        Self {
+            chosen,
+            dir,
+            entries: vec![],
+            visible: false,
+            selected: 0,
+            top: 0,
+            message: String::new(),
+        }
+    }
+
+    fn rows() -> usize {
+        ((screen_height() - MARGIN * 2.) / LINE_HEIGHT).max(4.) as usize - 3
+    }
+
+    /// Directories first, then the cartridges, by name.
+    fn open(&mut self, dir: PathBuf) {
+        let read = match std::fs::read_dir(&dir) {
+            Ok(read) => read,
+            Err(err) => {
+                self.message = format!("Couldn't open '{}': {err}.", dir.display());
+                return;
+            }
+        };
+        let mut entries: Vec<Entry> = read
+            .filter_map(Result::ok)
+            .map(|entry| {
+                let path = entry.path();
+                Entry {
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    dir: path.is_dir(),
+                    path,
+                }
+            })
+            .filter(|entry| entry.dir || is_cartridge(&entry.path))
+            .collect();
+        entries.sort_by(|a, b| {
+            b.dir
+                .cmp(&a.dir)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        if let Some(parent) = dir.parent() {
+            entries.insert(
+                0,
+                Entry {
+                    name: "..".to_string(),
+                    path: parent.to_path_buf(),
+                    dir: true,
+                },
+            );
+        }
+        self.dir = dir;
+        self.entries = entries;
+        self.selected = 0;
+        self.top = 0;
+        self.message.clear();
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected.min(self.entries.len().saturating_sub(1));
+        let rows = Self::rows();
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + rows {
+            self.top = self.selected + 1 - rows;
+        }
+    }
+
+    fn keys(&mut self) {
+        let rows = Self::rows();
+        if is_key_pressed(KeyCode::Up) {
+            self.select(self.selected.saturating_sub(1));
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.select(self.selected + 1);
+        }
+        if is_key_pressed(KeyCode::PageUp) {
+            self.select(self.selected.saturating_sub(rows));
+        }
+        if is_key_pressed(KeyCode::PageDown) {
+            self.select(self.selected + rows);
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            if let Some(parent) = self.dir.parent() {
+                self.open(parent.to_path_buf());
+            }
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            if let Some(entry) = self.entries.get(self.selected) {
+                if entry.dir {
+                    self.open(entry.path.clone());
+                } else {
+                    *self.chosen.borrow_mut() = Some(entry.path.clone());
+                    self.visible = false;
+                }
+            }
+        }
+    }
+}
+
+impl Overlay for FileBrowser {
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn update(&mut self) -> bool {
+        if is_key_pressed(TOGGLE_KEY) {
+            self.visible = !self.visible;
+            if self.visible {
+                let dir = std::fs::canonicalize(&self.dir).unwrap_or_else(|_| self.dir.clone());
+                self.open(dir);
+            }
+        }
+        if !self.visible {
+            return false;
+        }
+
+        self.keys();
+        while get_char_pressed().is_some() {}
+        true
+    }
+
+    fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        draw_rectangle(
+            0.,
+            0.,
+            screen_width(),
+            screen_height(),
+            Color::new(0., 0., 0., 0.85),
+        );
+        let text_y = |row: usize| MARGIN + LINE_HEIGHT * (row as f32 + 1.) - 4.;
+
+        draw_text(HELP, MARGIN, text_y(0), FONT_SIZE, WHITE);
+        let status = if self.message.is_empty() {
+            self.dir.display().to_string()
+        } else {
+            self.message.clone()
+        };
+        draw_text(&status, MARGIN, text_y(1), FONT_SIZE, GRAY);
+
+        for (row, (index, entry)) in self
+            .entries
+            .iter()
+            .enumerate()
+            .skip(self.top)
+            .take(Self::rows())
+            .enumerate()
+        {
+            let color = match (index == self.selected, entry.dir) {
+                (true, _) => YELLOW,
+                (false, true) => SKYBLUE,
+                (false, false) => WHITE,
+            };
+            let name = if entry.dir {
+                format!("{}/", entry.name)
+            } else {
+                entry.name.clone()
+            };
+            draw_text(&name, MARGIN, text_y(row + 3), FONT_SIZE, color);
+        }
+    }
+}
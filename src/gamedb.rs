@@ -0,0 +1,136 @@
+//! Known cartridges, keyed by the CRC32 of their ROM data, the LNX header
+//! left out so that headered and headerless dumps of a game share an entry.
+//!
+//! The entries are read from `gamedb.json` in the user configuration
+//! directory, no dump is known out of the box. An entry fixes the header of
+//! mis-tagged dumps when they are loaded and names the window:
+//!
+//! ```json
+//! [
+//!   {
+//!     "crc32": "0123abcd",
+//!     "title": "Game",
+//!     "publisher": "Publisher",
+//!     "rotation": "left",
+//!     "eeprom": "93C46",
+//!     "players": 2
+//!   }
+//! ]
+//! ```
+//!
+//! Only `crc32` and `title` are required.
+
+use crate::{
+    bindings, info,
+    lyx::{self, BankSizes, HeaderFields},
+};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::OnceLock};
+
+const ROTATION_OFFSET: usize = 58;
+const EEPROM_OFFSET: usize = 60;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Game {
+    pub(crate) crc32: String,
+    pub(crate) title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) publisher: Option<String>,
+    /// `none`, `left` or `right`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) rotation: Option<String>,
+    /// `none`, `93C46`, `93C56`, `93C66`, `93C76` or `93C86`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) eeprom: Option<String>,
+    /// Consoles a Comlynx game can be played with, a game for more than one
+    /// loaded without the link gets a warning.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) players: Option<u8>,
+}
+
+fn parse(data: &str, origin: &str) -> Vec<Game> {
+    serde_json::from_str(data).unwrap_or_else(|err| {
+        println!("Game database {origin}: {err}.");
+        vec![]
+    })
+}
+
+fn database() -> &'static [Game] {
+    static DATABASE: OnceLock<Vec<Game>> = OnceLock::new();
+    DATABASE.get_or_init(|| {
+        let Some(path) = bindings::config_dir().map(|dir| dir.join("gamedb.json")) else {
+            return vec![];
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(data) => parse(&data, &path.display().to_string()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(err) => {
+                println!("Game database {}: {err}.", path.display());
+                vec![]
+            }
+        }
+    })
+}
+
+/// CRC32 of the cartridge data, without the LNX header.
+pub(crate) fn crc32(data: &[u8]) -> String {
+    format!("{:08x}", crc32fast::hash(info::rom_data(data)))
+}
+
+pub(crate) fn lookup(data: &[u8]) -> Option<&'static Game> {
+    let crc32 = crc32(data);
+    database()
+        .iter()
+        .find(|game| game.crc32.eq_ignore_ascii_case(&crc32))
+}
+
+/// Corrects the LNX header with what the database knows of the game.
+fn fix(data: &mut [u8], game: &Game) {
+    if info::parse_lnx(data).is_none() {
+        return;
+    }
+    if let Some(rotation) = game.rotation.as_deref() {
+        match info::rotation_value(rotation) {
+            Some(value) => data[ROTATION_OFFSET] = value,
+            None => println!(
+                "Game database {}: unknown rotation '{rotation}'.",
+                game.crc32
+            ),
+        }
+    }
+    if let Some(eeprom) = game.eeprom.as_deref() {
+        match info::eeprom_value(eeprom) {
+            Some(value) => data[EEPROM_OFFSET] = (data[EEPROM_OFFSET] & !0x07) | value,
+            None => println!("Game database {}: unknown EEPROM '{eeprom}'.", game.crc32),
+        }
+    }
+}
+
+/// Reads a cartridge file, raw dumps given a header, the header fixed by the
+/// database.
+pub(crate) fn read_cartridge(path: &Path, banks: Option<BankSizes>) -> std::io::Result<Vec<u8>> {
+    let mut data = std::fs::read(path)?;
+    if lyx::is_raw(&data) {
+        data = lyx::to_lnx(&data, banks, &HeaderFields::default())
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    }
+    if let Some(game) = lookup(&data) {
+        fix(&mut data, game);
+    }
+    Ok(data)
+}
+
+/// The game in a cartridge file, when it is known.
+pub(crate) fn game(cartridge: &Path) -> Option<&'static Game> {
+    std::fs::read(cartridge).ok().and_then(|data| lookup(&data))
+}
+
+/// Title of the game in a cartridge file, when it is known.
+pub(crate) fn title(cartridge: &Path) -> Option<String> {
+    game(cartridge).map(|game| game.title.clone())
+}
+
+/// Window title, the game title when it is known.
+pub(crate) fn window_title(cartridge: &Path) -> String {
+    title(cartridge).map_or("Holani".to_string(), |title| format!("{title} - Holani"))
+}
@@ -0,0 +1,261 @@
+//! Emulator actions bound to keys, next to the Lynx buttons.
+
+use crate::{
+    keycodes::KeyChord,
+    runner::{runner_config::RunnerConfig, RunnerCommand},
+    screenshot,
+};
+use macroquad::window::set_fullscreen;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Hotkey {
+    Quit,
+    Pause,
+    FrameAdvance,
+    Mute,
+    Screenshot,
+    SaveState,
+    LoadState,
+    Reset,
+    PowerCycle,
+    Fullscreen,
+}
+
+pub(crate) type HotkeyMapping = HashMap<KeyChord, Hotkey>;
+
+impl Hotkey {
+    pub(crate) const ALL: [Hotkey; 10] = [
+        Hotkey::Quit,
+        Hotkey::Pause,
+        Hotkey::FrameAdvance,
+        Hotkey::Mute,
+        Hotkey::Screenshot,
+        Hotkey::SaveState,
+        Hotkey::LoadState,
+        Hotkey::Reset,
+        Hotkey::PowerCycle,
+        Hotkey::Fullscreen,
+    ];
+
+    /// Name understood by `from_str`.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Hotkey::Quit => "quit",
+            Hotkey::Pause => "pause",
+            Hotkey::FrameAdvance => "frame_advance",
+            Hotkey::Mute => "mute",
+            Hotkey::Screenshot => "screenshot",
+            Hotkey::SaveState => "save_state",
+            Hotkey::LoadState => "load_state",
+            Hotkey::Reset => "reset",
+            Hotkey::PowerCycle => "power_cycle",
+            Hotkey::Fullscreen => "fullscreen",
+        }
+    }
+
+    fn default_key(self) -> &'static str {
+        match self {
+            Hotkey::Quit => "ctrl+q",
+            Hotkey::Pause => "ctrl+p",
+            Hotkey::FrameAdvance => "ctrl+n",
+            Hotkey::Mute => "ctrl+m",
+            Hotkey::Screenshot => "f12",
+            Hotkey::SaveState => "ctrl+s",
+            Hotkey::LoadState => "ctrl+l",
+            Hotkey::Reset => "ctrl+r",
+            Hotkey::PowerCycle => "ctrl+shift+r",
+            Hotkey::Fullscreen => "f11",
+        }
+    }
+
+    pub(crate) fn defaults() -> HotkeyMapping {
+        Self::ALL
+            .iter()
+            .filter_map(|hotkey| Some((hotkey.default_key().parse().ok()?, *hotkey)))
+            .collect()
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|hotkey| hotkey.name() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown hotkey '{s}'."))
+    }
+}
+
+enum Reply {
+    /// The message is printed when the command succeeds.
+    Outcome(kanal::Receiver<Result<(), String>>, Option<String>),
+    Screen(kanal::Receiver<Vec<u8>>),
+}
+
+/// A runner command waiting for its reply.
+struct Pending {
+    hotkey: Hotkey,
+    reply: Reply,
+}
+
+impl Pending {
+    /// `None` while the reply hasn't come.
+    fn finish(&self, cartridge: &Path) -> Option<Result<(), String>> {
+        let cancelled = || "The request was cancelled.".to_string();
+        match &self.reply {
+            Reply::Outcome(reply_rx, done) => match reply_rx.try_recv() {
+                Ok(None) => None,
+                Ok(Some(result)) => Some(result.map(|()| {
+                    if let Some(done) = done {
+                        println!("{done}");
+                    }
+                })),
+                Err(_) => Some(Err(cancelled())),
+            },
+            Reply::Screen(reply_rx) => match reply_rx.try_recv() {
+                Ok(None) => None,
+                Ok(Some(rgba)) => Some(save_screenshot(cartridge, &rgba)),
+                Err(_) => Some(Err(cancelled())),
+            },
+        }
+    }
+}
+
+/// `<cartridge>-<unix time>.png` in the working directory.
+fn save_screenshot(cartridge: &Path, rgba: &[u8]) -> Result<(), String> {
+    let stem = cartridge
+        .file_stem()
+        .map_or("holani".into(), |stem| stem.to_string_lossy());
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let path = PathBuf::from(format!("{stem}-{time}.png"));
+    screenshot::save(&path, rgba)?;
+    println!("Screenshot saved to {}.", path.display());
+    Ok(())
+}
+
+/// Carries out the hotkeys, the ones affecting the emulation through runner
+/// commands. Their replies are picked up by `poll`, the window never waits
+/// for the runner thread, which may be stopped in the debugger.
+pub(crate) struct HotkeyActions {
+    command_tx: kanal::Sender<RunnerCommand>,
+    cartridge: PathBuf,
+    pending: Vec<Pending>,
+    /// Started with `--mute`, there is no audio output to turn back on.
+    no_sound: bool,
+    muted: bool,
+    fullscreen: bool,
+}
+
+impl HotkeyActions {
+    pub(crate) fn new(command_tx: kanal::Sender<RunnerCommand>, config: &RunnerConfig) -> Self {
+        Self {
+            command_tx,
+            cartridge: config.cartridge().clone().unwrap_or_default(),
+            pending: vec![],
+            no_sound: config.mute(),
+            muted: config.mute(),
+            fullscreen: false,
+        }
+    }
+
+    /// Returns false when the emulator should quit.
+    pub(crate) fn trigger(&mut self, hotkey: Hotkey) -> bool {
+        let result = match hotkey {
+            Hotkey::Quit => return false,
+            // The runner knows whether it is paused, the control server and
+            // the debugger pause it too.
+            Hotkey::Pause => self.send(RunnerCommand::TogglePause),
+            Hotkey::FrameAdvance => {
+                // Not waiting for the frame, the window keeps drawing.
+                let (reply_tx, _) = kanal::bounded(1);
+                self.send(RunnerCommand::StepFrames(1, reply_tx))
+            }
+            Hotkey::Mute if self.no_sound => Err("Sound is disabled by --mute.".to_string()),
+            Hotkey::Mute => {
+                self.muted = !self.muted;
+                self.send(RunnerCommand::SetMute(self.muted))
+            }
+            Hotkey::Screenshot => {
+                let (reply_tx, reply_rx) = kanal::bounded(1);
+                self.send(RunnerCommand::ReadScreen(reply_tx))
+                    .map(|()| self.wait(hotkey, Reply::Screen(reply_rx)))
+            }
+            Hotkey::SaveState => {
+                let path = self.cartridge.with_extension("state");
+                let done = format!("State saved to {}.", path.display());
+                self.request(
+                    hotkey,
+                    |reply_tx| RunnerCommand::SaveState(path, reply_tx),
+                    Some(done),
+                )
+            }
+            Hotkey::LoadState => {
+                let path = self.cartridge.with_extension("state");
+                let done = format!("State loaded from {}.", path.display());
+                self.request(
+                    hotkey,
+                    |reply_tx| RunnerCommand::LoadState(path, reply_tx),
+                    Some(done),
+                )
+            }
+            Hotkey::Reset => self.request(hotkey, RunnerCommand::Reset, None),
+            Hotkey::PowerCycle => self.request(hotkey, RunnerCommand::PowerCycle, None),
+            Hotkey::Fullscreen => {
+                self.fullscreen = !self.fullscreen;
+                set_fullscreen(self.fullscreen);
+                Ok(())
+            }
+        };
+        if let Err(err) = result {
+            println!("{}: {err}", hotkey.name());
+        }
+        true
+    }
+
+    /// Handles the replies that came since the last frame.
+    pub(crate) fn poll(&mut self) {
+        let cartridge = &self.cartridge;
+        self.pending
+            .retain(|pending| match pending.finish(cartridge) {
+                None => true,
+                Some(result) => {
+                    if let Err(err) = result {
+                        println!("{}: {err}", pending.hotkey.name());
+                    }
+                    false
+                }
+            });
+    }
+
+    fn send(&self, command: RunnerCommand) -> Result<(), String> {
+        self.command_tx
+            .send(command)
+            .map_err(|_| "The emulation isn't running.".to_string())
+    }
+
+    fn wait(&mut self, hotkey: Hotkey, reply: Reply) {
+        self.pending.push(Pending { hotkey, reply });
+    }
+
+    fn request(
+        &mut self,
+        hotkey: Hotkey,
+        command: impl FnOnce(kanal::Sender<Result<(), String>>) -> RunnerCommand,
+        done: Option<String>,
+    ) -> Result<(), String> {
+        let (reply_tx, reply_rx) = kanal::bounded(1);
+        self.send(command(reply_tx))?;
+        self.wait(hotkey, Reply::Outcome(reply_rx, done));
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+//! `holani-min info`: what a cartridge file holds, read without starting the
+//! emulation.
+
+use crate::{
+    debugger::gdb::to_hex,
+    gamedb::{self, Game},
+    lyx::BankSizes,
+};
+use clap::Args;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::path::{Path, PathBuf};
+
+pub(crate) const LNX_MAGIC: &[u8; 4] = b"LYNX";
+pub(crate) const LNX_HEADER_SIZE: usize = 64;
+/// Pages per bank, the bank size is the page size times this.
+pub(crate) const PAGES_PER_BANK: usize = 256;
+const BLL_MAGIC: [u8; 2] = [0x80, 0x08];
+pub(crate) const BLL_HEADER_SIZE: usize = 10;
+/// Header values of the rotations.
+const ROTATIONS: [&str; 3] = ["none", "left", "right"];
+/// Header values of the EEPROM types, in the low 3 bits.
+const EEPROMS: [&str; 6] = ["none", "93C46", "93C56", "93C66", "93C76", "93C86"];
+
+#[derive(Args)]
+pub(crate) struct InfoArgs {
+    /// Cartridge, .lnx, .o or raw .lyx file
+    file: PathBuf,
+
+    /// JSON output
+    #[arg(long, default_value_t = false)]
+    json: bool,
+
+    /// Print a game database entry for the file, from its header
+    #[arg(long, default_value_t = false, conflicts_with = "json")]
+    gamedb: bool,
+}
+
+/// The 64 bytes header of `.lnx` files.
+#[derive(Serialize)]
+pub(crate) struct LnxHeader {
+    pub(crate) name: String,
+    pub(crate) manufacturer: String,
+    pub(crate) version: u16,
+    /// Page sizes of bank 0 and 1, 0 when the bank is missing.
+    pub(crate) bank0_page_size: u16,
+    pub(crate) bank1_page_size: u16,
+    pub(crate) rotation: &'static str,
+    pub(crate) audin: bool,
+    pub(crate) eeprom: &'static str,
+    pub(crate) eeprom_sd: bool,
+    pub(crate) eeprom_8bit: bool,
+}
+
+/// The 10 bytes header of the BLL `.o` executables.
+#[derive(Serialize)]
+pub(crate) struct BllHeader {
+    pub(crate) load_address: u16,
+    pub(crate) size: u16,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "format", rename_all = "snake_case")]
+pub(crate) enum Format {
+    Lnx(LnxHeader),
+    Bll(BllHeader),
+    Unknown,
+}
+
+#[derive(Serialize)]
+struct CartridgeInfo {
+    file: PathBuf,
+    size: usize,
+    crc32: String,
+    sha1: String,
+    /// Without the LNX header, the game database key.
+    data_crc32: String,
+    #[serde(flatten)]
+    format: Format,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    game: Option<Game>,
+}
+
+fn text(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+fn name(names: &[&'static str], value: u8) -> &'static str {
+    names.get(usize::from(value)).copied().unwrap_or("unknown")
+}
+
+fn value(names: &[&str], name: &str) -> Option<u8> {
+    names
+        .iter()
+        .position(|n| n.eq_ignore_ascii_case(name))
+        .and_then(|value| u8::try_from(value).ok())
+}
+
+pub(crate) fn rotation_value(rotation: &str) -> Option<u8> {
+    value(&ROTATIONS, rotation)
+}
+
+pub(crate) fn eeprom_value(eeprom: &str) -> Option<u8> {
+    value(&EEPROMS, eeprom)
+}
+
+pub(crate) fn parse_lnx(data: &[u8]) -> Option<LnxHeader> {
+    if data.len() < LNX_HEADER_SIZE || &data[..4] != LNX_MAGIC {
+        return None;
+    }
+    let word = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    Some(LnxHeader {
+        bank0_page_size: word(4),
+        bank1_page_size: word(6),
+        version: word(8),
+        name: text(&data[10..42]),
+        manufacturer: text(&data[42..58]),
+        rotation: name(&ROTATIONS, data[58]),
+        audin: data[59] != 0,
+        eeprom: name(&EEPROMS, data[60] & 0x07),
+        eeprom_sd: data[60] & 0x40 != 0,
+        eeprom_8bit: data[60] & 0x80 != 0,
+    })
+}
+
+pub(crate) fn parse_bll(data: &[u8]) -> Option<BllHeader> {
+    if data.len() < BLL_HEADER_SIZE || data[..2] != BLL_MAGIC {
+        return None;
+    }
+    Some(BllHeader {
+        load_address: u16::from_be_bytes([data[2], data[3]]),
+        size: u16::from_be_bytes([data[4], data[5]]),
+    })
+}
+
+/// The cartridge data, after the LNX header if there is one.
+pub(crate) fn rom_data(data: &[u8]) -> &[u8] {
+    match parse_lnx(data) {
+        Some(_) => &data[LNX_HEADER_SIZE..],
+        None => data,
+    }
+}
+
+pub(crate) fn format(data: &[u8]) -> Format {
+    if let Some(header) = parse_lnx(data) {
+        Format::Lnx(header)
+    } else if let Some(header) = parse_bll(data) {
+        Format::Bll(header)
+    } else {
+        Format::Unknown
+    }
+}
+
+fn load(path: &Path) -> Result<CartridgeInfo, String> {
+    let data =
+        std::fs::read(path).map_err(|err| format!("Couldn't read '{}': {err}.", path.display()))?;
+    Ok(CartridgeInfo {
+        file: path.to_path_buf(),
+        size: data.len(),
+        crc32: format!("{:08x}", crc32fast::hash(&data)),
+        sha1: to_hex(&Sha1::digest(&data)),
+        data_crc32: gamedb::crc32(&data),
+        format: format(&data),
+        game: gamedb::lookup(&data).cloned(),
+    })
+}
+
+fn bank(page_size: u16) -> String {
+    match page_size {
+        0 => "-".to_string(),
+        size => format!(
+            "{size} bytes pages, {} KiB",
+            usize::from(size) * PAGES_PER_BANK / 1024
+        ),
+    }
+}
+
+fn print(info: &CartridgeInfo) {
+    println!("File:          {}", info.file.display());
+    println!("Size:          {} bytes", info.size);
+    println!("CRC32:         {}", info.crc32);
+    println!("SHA1:          {}", info.sha1);
+    println!("Data CRC32:    {}", info.data_crc32);
+    match &info.format {
+        Format::Lnx(header) => {
+            println!("Format:        LNX, version {}", header.version);
+            println!("Name:          {}", header.name);
+            println!("Manufacturer:  {}", header.manufacturer);
+            println!("Bank 0:        {}", bank(header.bank0_page_size));
+            println!("Bank 1:        {}", bank(header.bank1_page_size));
+            println!("Rotation:      {}", header.rotation);
+            println!("AUDIN:         {}", if header.audin { "yes" } else { "no" });
+            let mut eeprom = header.eeprom.to_string();
+            if header.eeprom_8bit {
+                eeprom.push_str(", 8-bit");
+            }
+            if header.eeprom_sd {
+                eeprom.push_str(", SD");
+            }
+            println!("EEPROM:        {eeprom}");
+        }
+        Format::Bll(header) => {
+            println!("Format:        BLL executable");
+            println!("Load address:  ${:04X}", header.load_address);
+            println!("Load size:     {} bytes", header.size);
+        }
+        Format::Unknown => match BankSizes::detect(info.size) {
+            Some(banks) => {
+                println!("Format:        raw dump");
+                println!("Bank 0:        {}", bank(banks.bank0));
+                println!("Bank 1:        {}", bank(banks.bank1));
+            }
+            None => println!("Format:        unknown, or a raw dump of an unusual size"),
+        },
+    }
+    match &info.game {
+        None => println!("Game:          not in the database"),
+        Some(game) => {
+            println!("Game:          {}", game.title);
+            for (what, value) in [
+                ("Publisher:", game.publisher.clone()),
+                ("Rotation:", game.rotation.clone()),
+                ("EEPROM:", game.eeprom.clone()),
+                ("Players:", game.players.map(|players| players.to_string())),
+            ] {
+                if let Some(value) = value {
+                    println!("  {what:<12} {value}");
+                }
+            }
+        }
+    }
+}
+
+/// The database entry of the game, or one made from the LNX header.
+fn gamedb_entry(info: &CartridgeInfo) -> Game {
+    if let Some(game) = &info.game {
+        return game.clone();
+    }
+    let known = |value: &str| (value != "none" && value != "unknown").then(|| value.to_string());
+    let stem = info
+        .file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match &info.format {
+        Format::Lnx(header) => Game {
+            crc32: info.data_crc32.clone(),
+            title: if header.name.is_empty() {
+                stem
+            } else {
+                header.name.clone()
+            },
+            publisher: (!header.manufacturer.is_empty()).then(|| header.manufacturer.clone()),
+            rotation: known(header.rotation),
+            eeprom: known(header.eeprom),
+            players: None,
+        },
+        _ => Game {
+            crc32: info.data_crc32.clone(),
+            title: stem,
+            publisher: None,
+            rotation: None,
+            eeprom: None,
+            players: None,
+        },
+    }
+}
+
+pub(crate) fn run(args: &InfoArgs) {
+    let info = load(&args.file).unwrap_or_else(|err| {
+        println!("Error: {err}");
+        std::process::exit(1);
+    });
+    if args.gamedb {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&gamedb_entry(&info)).unwrap_or_default()
+        );
+    } else if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&info).unwrap_or_default()
+        );
+    } else {
+        print(&info);
+    }
+}
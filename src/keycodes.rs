@@ -1,58 +1,327 @@
-use macroquad::input::KeyCode;
+use macroquad::input::{is_key_down, is_key_pressed, KeyCode};
+use std::{fmt::Display, str::FromStr};
+
+/// Names of each key, case insensitive. The first one is used when printing
+/// or saving a binding, the others are aliases.
+const KEYS: &[(KeyCode, &[&str])] = &[
+    (KeyCode::Key0, &["0"]),
+    (KeyCode::Key1, &["1"]),
+    (KeyCode::Key2, &["2"]),
+    (KeyCode::Key3, &["3"]),
+    (KeyCode::Key4, &["4"]),
+    (KeyCode::Key5, &["5"]),
+    (KeyCode::Key6, &["6"]),
+    (KeyCode::Key7, &["7"]),
+    (KeyCode::Key8, &["8"]),
+    (KeyCode::Key9, &["9"]),
+    (KeyCode::A, &["a"]),
+    (KeyCode::B, &["b"]),
+    (KeyCode::C, &["c"]),
+    (KeyCode::D, &["d"]),
+    (KeyCode::E, &["e"]),
+    (KeyCode::F, &["f"]),
+    (KeyCode::G, &["g"]),
+    (KeyCode::H, &["h"]),
+    (KeyCode::I, &["i"]),
+    (KeyCode::J, &["j"]),
+    (KeyCode::K, &["k"]),
+    (KeyCode::L, &["l"]),
+    (KeyCode::M, &["m"]),
+    (KeyCode::N, &["n"]),
+    (KeyCode::O, &["o"]),
+    (KeyCode::P, &["p"]),
+    (KeyCode::Q, &["q"]),
+    (KeyCode::R, &["r"]),
+    (KeyCode::S, &["s"]),
+    (KeyCode::T, &["t"]),
+    (KeyCode::U, &["u"]),
+    (KeyCode::V, &["v"]),
+    (KeyCode::W, &["w"]),
+    (KeyCode::X, &["x"]),
+    (KeyCode::Y, &["y"]),
+    (KeyCode::Z, &["z"]),
+    (KeyCode::Apostrophe, &["'", "apostrophe", "quote"]),
+    (KeyCode::Backslash, &["\\", "backslash"]),
+    (KeyCode::Comma, &[",", "comma"]),
+    (KeyCode::Equal, &["=", "equal", "equals"]),
+    (KeyCode::LeftBracket, &["[", "leftbracket", "lbracket"]),
+    (KeyCode::Minus, &["-", "minus"]),
+    (KeyCode::Period, &[".", "period", "dot"]),
+    (KeyCode::RightBracket, &["]", "rightbracket", "rbracket"]),
+    (KeyCode::Semicolon, &[";", "semicolon"]),
+    (KeyCode::Slash, &["/", "slash"]),
+    (
+        KeyCode::GraveAccent,
+        &["`", "graveaccent", "grave", "backtick"],
+    ),
+    (KeyCode::World1, &["world1"]),
+    (KeyCode::World2, &["world2"]),
+    (KeyCode::Up, &["up"]),
+    (KeyCode::Down, &["down"]),
+    (KeyCode::Left, &["left"]),
+    (KeyCode::Right, &["right"]),
+    (KeyCode::Space, &["space"]),
+    (KeyCode::Enter, &["enter", "return"]),
+    (KeyCode::Tab, &["tab"]),
+    (KeyCode::Escape, &["escape", "esc"]),
+    (KeyCode::Backspace, &["backspace"]),
+    (KeyCode::Insert, &["insert", "ins"]),
+    (KeyCode::Delete, &["delete", "del"]),
+    (KeyCode::PageUp, &["pageup", "pgup"]),
+    (KeyCode::PageDown, &["pagedown", "pgdn"]),
+    (KeyCode::Home, &["home"]),
+    (KeyCode::End, &["end"]),
+    (KeyCode::CapsLock, &["capslock"]),
+    (KeyCode::ScrollLock, &["scrolllock"]),
+    (KeyCode::NumLock, &["numlock"]),
+    (KeyCode::PrintScreen, &["printscreen", "prtsc"]),
+    (KeyCode::Pause, &["pause", "break"]),
+    (KeyCode::F1, &["f1"]),
+    (KeyCode::F2, &["f2"]),
+    (KeyCode::F3, &["f3"]),
+    (KeyCode::F4, &["f4"]),
+    (KeyCode::F5, &["f5"]),
+    (KeyCode::F6, &["f6"]),
+    (KeyCode::F7, &["f7"]),
+    (KeyCode::F8, &["f8"]),
+    (KeyCode::F9, &["f9"]),
+    (KeyCode::F10, &["f10"]),
+    (KeyCode::F11, &["f11"]),
+    (KeyCode::F12, &["f12"]),
+    (KeyCode::F13, &["f13"]),
+    (KeyCode::F14, &["f14"]),
+    (KeyCode::F15, &["f15"]),
+    (KeyCode::F16, &["f16"]),
+    (KeyCode::F17, &["f17"]),
+    (KeyCode::F18, &["f18"]),
+    (KeyCode::F19, &["f19"]),
+    (KeyCode::F20, &["f20"]),
+    (KeyCode::F21, &["f21"]),
+    (KeyCode::F22, &["f22"]),
+    (KeyCode::F23, &["f23"]),
+    (KeyCode::F24, &["f24"]),
+    (KeyCode::F25, &["f25"]),
+    (KeyCode::Kp0, &["kp0", "numpad0"]),
+    (KeyCode::Kp1, &["kp1", "numpad1"]),
+    (KeyCode::Kp2, &["kp2", "numpad2"]),
+    (KeyCode::Kp3, &["kp3", "numpad3"]),
+    (KeyCode::Kp4, &["kp4", "numpad4"]),
+    (KeyCode::Kp5, &["kp5", "numpad5"]),
+    (KeyCode::Kp6, &["kp6", "numpad6"]),
+    (KeyCode::Kp7, &["kp7", "numpad7"]),
+    (KeyCode::Kp8, &["kp8", "numpad8"]),
+    (KeyCode::Kp9, &["kp9", "numpad9"]),
+    (KeyCode::KpDecimal, &["kpdecimal", "kp."]),
+    (KeyCode::KpDivide, &["kpdivide", "kp/"]),
+    (KeyCode::KpMultiply, &["kpmultiply", "kp*"]),
+    (KeyCode::KpSubtract, &["kpsubtract", "kp-"]),
+    (KeyCode::KpAdd, &["kpadd", "kp+"]),
+    (KeyCode::KpEnter, &["kpenter"]),
+    (KeyCode::KpEqual, &["kpequal", "kp="]),
+    (KeyCode::LeftShift, &["leftshift", "lshift", "shift"]),
+    (
+        KeyCode::LeftControl,
+        &["leftcontrol", "lctrl", "ctrl", "control"],
+    ),
+    (KeyCode::LeftAlt, &["leftalt", "lalt", "alt"]),
+    (
+        KeyCode::LeftSuper,
+        &["leftsuper", "lsuper", "super", "cmd", "win"],
+    ),
+    (KeyCode::RightShift, &["rightshift", "rshift"]),
+    (KeyCode::RightControl, &["rightcontrol", "rctrl"]),
+    (KeyCode::RightAlt, &["rightalt", "ralt", "altgr"]),
+    (KeyCode::RightSuper, &["rightsuper", "rsuper"]),
+    (KeyCode::Menu, &["menu"]),
+    (KeyCode::Back, &["back"]),
+];
 
 pub fn translate_keycode(keycode: &str) -> KeyCode {
-    // same as GLFW
-    match keycode {
-        "0" => KeyCode::Key0,
-        "1" => KeyCode::Key1,
-        "2" => KeyCode::Key2,
-        "3" => KeyCode::Key3,
-        "4" => KeyCode::Key4,
-        "5" => KeyCode::Key5,
-        "6" => KeyCode::Key6,
-        "7" => KeyCode::Key7,
-        "8" => KeyCode::Key8,
-        "9" => KeyCode::Key9,
-        "a" => KeyCode::A,
-        "b" => KeyCode::B,
-        "c" => KeyCode::C,
-        "d" => KeyCode::D,
-        "e" => KeyCode::E,
-        "f" => KeyCode::F,
-        "g" => KeyCode::G,
-        "h" => KeyCode::H,
-        "i" => KeyCode::I,
-        "j" => KeyCode::J,
-        "k" => KeyCode::K,
-        "l" => KeyCode::L,
-        "m" => KeyCode::M,
-        "n" => KeyCode::N,
-        "o" => KeyCode::O,
-        "p" => KeyCode::P,
-        "q" => KeyCode::Q,
-        "r" => KeyCode::R,
-        "s" => KeyCode::S,
-        "t" => KeyCode::T,
-        "u" => KeyCode::U,
-        "v" => KeyCode::V,
-        "w" => KeyCode::W,
-        "x" => KeyCode::X,
-        "y" => KeyCode::Y,
-        "z" => KeyCode::Z,
-        "\'" => KeyCode::Apostrophe,
-        "\\" => KeyCode::Backslash,
-        "," => KeyCode::Comma,
-        "=" => KeyCode::Equal,
-        "[" => KeyCode::LeftBracket,
-        "-" => KeyCode::Minus,
-        "." => KeyCode::Period,
-        "]" => KeyCode::RightBracket,
-        ";" => KeyCode::Semicolon,
-        "/" => KeyCode::Slash,
-        "up" => KeyCode::Up,
-        "down" => KeyCode::Down,
-        "left" => KeyCode::Left,
-        "right" => KeyCode::Right,
-        _ => KeyCode::Unknown,
-    }
-}
\ No newline at end of file
+    let keycode = keycode.to_ascii_lowercase();
+    KEYS.iter()
+        .find(|(_, names)| names.contains(&keycode.as_str()))
+        .map_or(KeyCode::Unknown, |(key, _)| *key)
+}
+
+/// Name of a key, as understood by `translate_keycode`.
+pub fn keycode_name(key: KeyCode) -> Option<&'static str> {
+    KEYS.iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, names)| names[0])
+}
+
+/// Modifier keys, left or right.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl Modifiers {
+    pub fn held() -> Self {
+        let down = |left, right| is_key_down(left) || is_key_down(right);
+        Self {
+            ctrl: down(KeyCode::LeftControl, KeyCode::RightControl),
+            shift: down(KeyCode::LeftShift, KeyCode::RightShift),
+            alt: down(KeyCode::LeftAlt, KeyCode::RightAlt),
+            logo: down(KeyCode::LeftSuper, KeyCode::RightSuper),
+        }
+    }
+
+    fn is_empty(self) -> bool {
+        self == Self::default()
+    }
+
+    fn contains(self, other: Self) -> bool {
+        (self.ctrl || !other.ctrl)
+            && (self.shift || !other.shift)
+            && (self.alt || !other.alt)
+            && (self.logo || !other.logo)
+    }
+
+    /// Sets the modifier called `name`, returns false when it isn't one.
+    fn set(&mut self, name: &str) -> bool {
+        match name.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => self.ctrl = true,
+            "shift" => self.shift = true,
+            "alt" => self.alt = true,
+            "super" | "cmd" | "win" => self.logo = true,
+            _ => return false,
+        }
+        true
+    }
+}
+
+pub fn is_modifier(key: KeyCode) -> bool {
+    matches!(
+        key,
+        KeyCode::LeftControl
+            | KeyCode::RightControl
+            | KeyCode::LeftShift
+            | KeyCode::RightShift
+            | KeyCode::LeftAlt
+            | KeyCode::RightAlt
+            | KeyCode::LeftSuper
+            | KeyCode::RightSuper
+    )
+}
+
+/// A key, alone or with modifiers: `w`, `ctrl+s`, `ctrl+shift+f1`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+impl KeyChord {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    /// True while the key and the modifiers are down. A key bound alone
+    /// doesn't count while one of `chords` using it with modifiers is held.
+    pub fn is_held<'a>(&self, chords: impl IntoIterator<Item = &'a KeyChord>) -> bool {
+        if !is_key_down(self.key) {
+            return false;
+        }
+        let held = Modifiers::held();
+        if !self.modifiers.is_empty() {
+            return held.contains(self.modifiers);
+        }
+        !chords.into_iter().any(|chord| {
+            chord.key == self.key && !chord.modifiers.is_empty() && held.contains(chord.modifiers)
+        })
+    }
+
+    /// Like `is_held`, only on the frame the key goes down.
+    pub fn is_pressed<'a>(&self, chords: impl IntoIterator<Item = &'a KeyChord>) -> bool {
+        is_key_pressed(self.key) && self.is_held(chords)
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = Modifiers::default();
+        let mut rest = s;
+        // `kp+` is a key, not a modifier list.
+        while let Some((modifier, tail)) = rest.split_once('+') {
+            if tail.is_empty() || !modifiers.set(modifier) {
+                break;
+            }
+            rest = tail;
+        }
+        match translate_keycode(rest) {
+            KeyCode::Unknown => Err(format!("Unknown key '{s}'.")),
+            key => Ok(Self { key, modifiers }),
+        }
+    }
+}
+
+impl Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (held, name) in [
+            (self.modifiers.ctrl, "ctrl"),
+            (self.modifiers.shift, "shift"),
+            (self.modifiers.alt, "alt"),
+            (self.modifiers.logo, "super"),
+        ] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+        write!(f, "{}", keycode_name(self.key).unwrap_or("unknown"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_modifiers() -> impl Iterator<Item = Modifiers> {
+        (0..16).map(|bits: u8| Modifiers {
+            ctrl: bits & 1 != 0,
+            shift: bits & 2 != 0,
+            alt: bits & 4 != 0,
+            logo: bits & 8 != 0,
+        })
+    }
+
+    #[test]
+    fn chords_round_trip() {
+        for (key, names) in KEYS {
+            for name in *names {
+                assert_eq!(name.parse(), Ok(KeyChord::new(*key)), "{name}");
+            }
+            for modifiers in all_modifiers() {
+                let chord = KeyChord {
+                    key: *key,
+                    modifiers,
+                };
+                assert_eq!(chord.to_string().parse(), Ok(chord), "{chord}");
+            }
+        }
+    }
+
+    #[test]
+    fn keypad_plus_is_a_key() {
+        assert_eq!("kp+".parse(), Ok(KeyChord::new(KeyCode::KpAdd)));
+        assert_eq!(
+            "ctrl+kp+".parse(),
+            Ok(KeyChord {
+                key: KeyCode::KpAdd,
+                modifiers: Modifiers {
+                    ctrl: true,
+                    ..Modifiers::default()
+                },
+            })
+        );
+        assert!("ctrl+".parse::<KeyChord>().is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! Raw cartridge dumps, `.lyx`: the ROM data without the LNX header. They are
+//! given a header when loaded, and `holani-min convert` writes it to a `.lnx`.
+
+use crate::{
+    gamedb,
+    info::{self, PAGES_PER_BANK},
+};
+use clap::Args;
+use std::{path::PathBuf, str::FromStr};
+
+/// Page sizes of the cartridges that were made.
+const PAGE_SIZES: [u16; 4] = [256, 512, 1024, 2048];
+const LARGEST_BANK: usize = 2048 * PAGES_PER_BANK;
+
+/// Page sizes of both banks, 0 when the bank is missing.
+#[derive(Clone, Copy)]
+pub(crate) struct BankSizes {
+    pub(crate) bank0: u16,
+    pub(crate) bank1: u16,
+}
+
+impl BankSizes {
+    /// Guesses the banks from the dump size: one bank up to 512K, then a
+    /// full bank 0 and the rest in bank 1.
+    pub(crate) fn detect(len: usize) -> Option<Self> {
+        let page = |size: usize| {
+            PAGE_SIZES
+                .iter()
+                .copied()
+                .find(|page| usize::from(*page) * PAGES_PER_BANK == size)
+        };
+        if let Some(bank0) = page(len) {
+            return Some(Self { bank0, bank1: 0 });
+        }
+        let bank1 = page(len.checked_sub(LARGEST_BANK)?)?;
+        Some(Self {
+            bank0: PAGE_SIZES[PAGE_SIZES.len() - 1],
+            bank1,
+        })
+    }
+}
+
+impl FromStr for BankSizes {
+    type Err = String;
+
+    /// `<bank0>[,<bank1>]`, sizes in bytes or with a `K` suffix, e.g. `256K`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let page = |size: &str| -> Result<u16, String> {
+            let size = size.trim();
+            let bytes = match size.strip_suffix(['K', 'k']) {
+                Some(kib) => kib.parse::<usize>().map(|kib| kib * 1024),
+                None => size.parse::<usize>(),
+            }
+            .map_err(|_| format!("Invalid bank size '{size}'."))?;
+            if bytes == 0 {
+                return Ok(0);
+            }
+            PAGE_SIZES
+                .iter()
+                .copied()
+                .find(|page| usize::from(*page) * PAGES_PER_BANK == bytes)
+                .ok_or_else(|| {
+                    format!("Bank size '{size}' isn't one of 0, 64K, 128K, 256K or 512K.")
+                })
+        };
+        let (bank0, bank1) = s.split_once(',').unwrap_or((s, "0"));
+        let banks = Self {
+            bank0: page(bank0)?,
+            bank1: page(bank1)?,
+        };
+        if banks.bank0 == 0 {
+            return Err("Bank 0 can't be empty.".to_string());
+        }
+        Ok(banks)
+    }
+}
+
+/// The LNX header fields a raw dump doesn't have.
+#[derive(Default)]
+pub(crate) struct HeaderFields<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) manufacturer: &'a str,
+    pub(crate) rotation: u8,
+    pub(crate) eeprom: u8,
+}
+
+/// Copies `text` into a NUL padded field, truncated to leave a NUL.
+fn text(field: &mut [u8], text: &str) {
+    let len = text.len().min(field.len() - 1);
+    field[..len].copy_from_slice(&text.as_bytes()[..len]);
+}
+
+fn header(banks: BankSizes, fields: &HeaderFields) -> Vec<u8> {
+    let mut header = vec![0; info::LNX_HEADER_SIZE];
+    header[..4].copy_from_slice(info::LNX_MAGIC);
+    header[4..6].copy_from_slice(&banks.bank0.to_le_bytes());
+    header[6..8].copy_from_slice(&banks.bank1.to_le_bytes());
+    header[8..10].copy_from_slice(&1u16.to_le_bytes());
+    text(&mut header[10..42], fields.name);
+    text(&mut header[42..58], fields.manufacturer);
+    header[58] = fields.rotation;
+    header[60] = fields.eeprom;
+    header
+}
+
+/// Raw dumps are the files that are neither `.lnx` nor `.o`.
+pub(crate) fn is_raw(data: &[u8]) -> bool {
+    matches!(info::format(data), info::Format::Unknown)
+}
+
+/// Prefixes a raw dump with an LNX header, the banks given or detected.
+pub(crate) fn to_lnx(
+    data: &[u8],
+    banks: Option<BankSizes>,
+    fields: &HeaderFields,
+) -> Result<Vec<u8>, String> {
+    let banks = match banks {
+        Some(banks) => {
+            let size = (usize::from(banks.bank0) + usize::from(banks.bank1)) * PAGES_PER_BANK;
+            if size != data.len() {
+                return Err(format!(
+                    "The bank sizes add up to {size} bytes, the dump has {}.",
+                    data.len()
+                ));
+            }
+            banks
+        }
+        None => BankSizes::detect(data.len()).ok_or_else(|| {
+            format!(
+                "Can't tell the bank sizes of a {} bytes dump, use --bank-size.",
+                data.len()
+            )
+        })?,
+    };
+    let mut lnx = header(banks, fields);
+    lnx.extend_from_slice(data);
+    Ok(lnx)
+}
+
+#[derive(Args)]
+pub(crate) struct ConvertArgs {
+    /// Raw cartridge dump, .lyx
+    input: PathBuf,
+
+    /// LNX file to write [default: <INPUT>.lnx]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Bank sizes <BANK0>[,<BANK1>], in bytes or K [default: from the dump size]
+    #[arg(long, value_name = "SIZE")]
+    bank_size: Option<BankSizes>,
+
+    /// Cartridge name [default: from the game database or the file name]
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Manufacturer [default: from the game database]
+    #[arg(long)]
+    manufacturer: Option<String>,
+
+    /// Screen rotation, none, left or right [default: from the game database or none]
+    #[arg(long)]
+    rotation: Option<String>,
+
+    /// EEPROM type, none, 93C46, 93C56, 93C66, 93C76 or 93C86 [default: from the game database or none]
+    #[arg(long)]
+    eeprom: Option<String>,
+}
+
+fn convert(args: &ConvertArgs) -> Result<PathBuf, String> {
+    let data = std::fs::read(&args.input)
+        .map_err(|err| format!("Couldn't read '{}': {err}.", args.input.display()))?;
+    if !is_raw(&data) {
+        return Err(format!("'{}' already has a header.", args.input.display()));
+    }
+
+    let game = gamedb::lookup(&data);
+    let stem = args
+        .input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = args
+        .name
+        .clone()
+        .or_else(|| game.map(|game| game.title.clone()))
+        .unwrap_or(stem);
+    let manufacturer = args
+        .manufacturer
+        .clone()
+        .or_else(|| game.and_then(|game| game.publisher.clone()))
+        .unwrap_or_default();
+    let rotation = args
+        .rotation
+        .clone()
+        .or_else(|| game.and_then(|game| game.rotation.clone()))
+        .map_or(Ok(0), |rotation| {
+            info::rotation_value(&rotation).ok_or_else(|| format!("Unknown rotation '{rotation}'."))
+        })?;
+    let eeprom = args
+        .eeprom
+        .clone()
+        .or_else(|| game.and_then(|game| game.eeprom.clone()))
+        .map_or(Ok(0), |eeprom| {
+            info::eeprom_value(&eeprom).ok_or_else(|| format!("Unknown EEPROM '{eeprom}'."))
+        })?;
+
+    let lnx = to_lnx(
+        &data,
+        args.bank_size,
+        &HeaderFields {
+            name: &name,
+            manufacturer: &manufacturer,
+            rotation,
+            eeprom,
+        },
+    )?;
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input.with_extension("lnx"));
+    std::fs::write(&output, lnx)
+        .map_err(|err| format!("Couldn't write '{}': {err}.", output.display()))?;
+    Ok(output)
+}
+
+pub(crate) fn run(args: &ConvertArgs) {
+    match convert(args) {
+        Ok(output) => println!("Written {}.", output.display()),
+        Err(err) => {
+            println!("Error: {err}");
+            std::process::exit(1);
+        }
+    }
+}
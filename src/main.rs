@@ -1,42 +1,101 @@
-use clap::Parser;
+use bindings::{panel::BindingsPanel, ButtonMapping};
+use cheats::panel::CheatPanel;
+use clap::{Parser, Subcommand};
+use control::ControlEndpoint;
+use debugger::{
+    symbols::Symbols,
+    trace::{self, TraceConfig},
+};
+use file_browser::{is_cartridge, FileBrowser};
 use holani::{
     cartridge::lnx_header::LNXRotation,
     mikey::video::{LYNX_SCREEN_HEIGHT, LYNX_SCREEN_WIDTH},
     suzy::registers::{Joystick, Switches},
 };
-use keycodes::translate_keycode;
+use hotkeys::{Hotkey, HotkeyActions, HotkeyMapping};
+use keycodes::{translate_keycode, KeyChord};
+use log::debug;
+use lyx::BankSizes;
 use macroquad::prelude::*;
+use memory_viewer::MemoryViewer;
+use mikey_viewer::MikeyViewer;
 use miniquad::window::screen_size;
+#[cfg(feature = "comlynx_external")]
+use netplay::{netplay_runner_thread::MAX_ROLLBACK, NetplayConfig};
+use overlay::Overlays;
+#[cfg(feature = "comlynx_external")]
+use runner::comlynx_transport::ComlynxEndpoint;
 use runner::{
+    load_lynx,
     runner_config::{Input, RunnerConfig},
-    Runner,
+    Runner, RunnerCommand,
 };
-use std::path::PathBuf;
+use sprite_viewer::SpriteViewer;
+use std::{cell::RefCell, ops::RangeInclusive, path::PathBuf, rc::Rc};
 
+pub(crate) mod bindings;
+pub(crate) mod cheats;
+pub(crate) mod control;
+pub(crate) mod debugger;
+pub(crate) mod file_browser;
+pub(crate) mod gamedb;
+pub(crate) mod hotkeys;
+pub(crate) mod info;
 pub(crate) mod keycodes;
+pub(crate) mod lyx;
+pub(crate) mod memory_viewer;
+pub(crate) mod mikey_viewer;
+pub(crate) mod netplay;
+pub(crate) mod overlay;
+pub(crate) mod regression;
 pub(crate) mod runner;
+pub(crate) mod screenshot;
+pub(crate) mod script;
 pub(crate) mod sound_source;
+pub(crate) mod sprite_viewer;
+
+const DEFAULT_BUTTONS: &str = "up,down,left,right,q,w,1,2,p";
 
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Cartright, can be .o or a .lnx file
-    #[arg(short, long)]
-    cartridge: PathBuf,
+    #[arg(short, long, required = true)]
+    cartridge: Option<PathBuf>,
+
+    /// Bank sizes of a raw .lyx dump <BANK0>[,<BANK1>], in bytes or K [default: from the dump size]
+    #[arg(long, value_name = "SIZE")]
+    bank_size: Option<BankSizes>,
 
     /// ROM override
     #[arg(short, long)]
     rom: Option<PathBuf>,
 
-    /// Buttons mapping <up>,<down>,<left>,<right>,<out>,<in>,<o1>,<o2>,<pause>
-    #[arg(
-        short,
-        long,
-        value_delimiter = ',',
-        default_value = "up,down,left,right,q,w,1,2,p"
-    )]
+    /// Buttons mapping <up>,<down>,<left>,<right>,<out>,<in>,<o1>,<o2>,<pause>, or <button>=<key>|<key>,... to change some of them [default: up,down,left,right,q,w,1,2,p]
+    #[arg(short, long, value_delimiter = ',')]
     buttons: Option<Vec<String>>,
 
+    /// Hotkeys mapping <hotkey>=<key>|<key>,..., see the README for the names and defaults
+    #[arg(long, value_delimiter = ',')]
+    hotkeys: Option<Vec<String>>,
+
+    /// Frames turbo buttons stay pressed, then released
+    #[arg(long, value_name = "FRAMES", default_value_t = 2, value_parser = clap::value_parser!(u32).range(1..))]
+    turbo_rate: u32,
+
+    /// Reset the console every <FRAMES> frames, for soak tests
+    #[arg(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(1..))]
+    reset_after: Option<u32>,
+
     /// Linear display filter
     #[arg(short, long, default_value_t = false)]
     linear: bool,
@@ -45,40 +104,171 @@ struct Args {
     #[arg(short, long, default_value_t = false)]
     mute: bool,
 
+    /// Debugger, controlled from the terminal
+    #[arg(short, long, default_value_t = false)]
+    debug: bool,
+
+    /// GDB remote debugging, listening on localhost:<GDB>
+    #[arg(long, conflicts_with = "debug")]
+    gdb: Option<u16>,
+
+    /// Symbols file, cc65 .dbg or VICE labels, for the debugger and traces
+    #[arg(long, value_name = "FILE")]
+    symbols: Option<PathBuf>,
+
+    /// Trace executed instructions to <TRACE>
+    #[arg(long)]
+    trace: Option<PathBuf>,
+
+    /// Trace only the instructions in <START>-<END>, hexadecimal addresses
+    #[arg(long, value_name = "START-END", requires = "trace", value_parser = trace::parse_address_range)]
+    trace_range: Option<RangeInclusive<u16>>,
+
+    /// Trace only the frames <FIRST>-<LAST>, <FIRST>- until the end
+    #[arg(long, value_name = "FIRST-LAST", requires = "trace", value_parser = trace::parse_frame_range)]
+    trace_frames: Option<RangeInclusive<u64>>,
+
+    /// Rhai script run alongside the emulation
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+
+    /// JSON-lines control server on <PORT> (localhost), tcp://<HOST>:<PORT> or unix:<PATH>
+    #[arg(long, value_name = "ENDPOINT")]
+    control: Option<ControlEndpoint>,
+
     /// Enable Comlynx
     #[cfg(not(feature = "comlynx_external"))]
     #[arg(short('x'), long, default_value_t = false)]
     comlynx: bool,
 
-    /// Comlynx tcp port
+    /// Comlynx endpoint: <port>, tcp://host:port, unix:/path or udp://peer:port[?bind=port]
+    #[cfg(feature = "comlynx_external")]
+    #[arg(short('x'), long, required_unless_present = "netplay")]
+    comlynx: Option<ComlynxEndpoint>,
+
+    /// Raw Comlynx byte stream, without handshake or framing
+    #[cfg(feature = "comlynx_external")]
+    #[arg(long, default_value_t = false)]
+    comlynx_raw: bool,
+
+    /// Comlynx lockstep, peers stay within <CYCLES> emulated cycles of each other
+    #[cfg(feature = "comlynx_external")]
+    #[arg(long, value_name = "CYCLES", num_args = 0..=1, default_missing_value = "16000")]
+    comlynx_lockstep: Option<u32>,
+
+    /// Rollback netplay, local UDP port to exchange inputs on
+    #[cfg(feature = "comlynx_external")]
+    #[arg(
+        long,
+        value_name = "PORT",
+        requires = "netplay_peer",
+        conflicts_with_all = ["comlynx", "debug", "gdb", "control", "script", "trace", "reset_after"]
+    )]
+    netplay: Option<u16>,
+
+    /// Rollback netplay peer address
+    #[cfg(feature = "comlynx_external")]
+    #[arg(long, value_name = "HOST:PORT")]
+    netplay_peer: Option<String>,
+
+    /// Rollback netplay local player, 1 or 2
+    #[cfg(feature = "comlynx_external")]
+    #[arg(long, value_name = "PLAYER", default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=2))]
+    netplay_player: u8,
+
+    /// Rollback netplay local input delay in frames
     #[cfg(feature = "comlynx_external")]
-    #[arg(short('x'), long)]
-    comlynx: u16,
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u8).range(0..=i64::from(MAX_ROLLBACK))
+    )]
+    netplay_delay: u8,
 }
 
-#[macroquad::main("Holani")]
-async fn main() {
+#[derive(Subcommand)]
+enum Command {
+    /// UDP proxy adding latency, jitter and loss between two netplay peers
+    Netproxy(netplay::proxy::ProxyArgs),
+    /// Disassemble a cartridge after running it for a few frames
+    Disasm(debugger::disasm::DisasmArgs),
+    /// Run the regression tests of a manifest headless
+    Test(regression::TestArgs),
+    /// Print the header and hashes of a cartridge file
+    Info(info::InfoArgs),
+    /// Wrap a raw .lyx dump into a .lnx file
+    Convert(lyx::ConvertArgs),
+}
+
+fn main() {
     env_logger::init();
-    let config = process_args();
+    let args = Args::parse();
 
-    let mut runner = Runner::new(config.clone());
-    let (input_tx, update_display_rx, rotation) = runner.initialize_thread();
+    match args.command {
+        Some(Command::Netproxy(proxy)) => netplay::proxy::run(&proxy),
+        Some(Command::Disasm(disasm)) => debugger::disasm::run(&disasm),
+        Some(Command::Test(test)) => regression::run(&test),
+        Some(Command::Info(info)) => info::run(&info),
+        Some(Command::Convert(convert)) => lyx::run(&convert),
+        None => {
+            let config = process_args(args);
+            #[cfg(not(feature = "comlynx_external"))]
+            warn_unlinked(&config);
+            let title = gamedb::window_title(config.cartridge().as_ref().unwrap());
+            macroquad::Window::new(&title, emulate(config));
+        }
+    }
+}
 
-    let mut joystick: Joystick = Joystick::empty();
-    let mut switches: Switches = Switches::empty();
+/// Multiplayer games wait for their peers, tells when they are loaded
+/// without the Comlynx link.
+#[cfg(not(feature = "comlynx_external"))]
+fn warn_unlinked(config: &RunnerConfig) {
+    if config.comlynx() {
+        return;
+    }
+    let Some(game) = config.cartridge().as_deref().and_then(gamedb::game) else {
+        return;
+    };
+    if let Some(players @ 2..) = game.players {
+        println!(
+            "{} is a {players} players Comlynx game, link the consoles with --comlynx.",
+            game.title
+        );
+    }
+}
 
-    let rgba_buffer: Vec<u8> = vec![255; (LYNX_SCREEN_WIDTH * LYNX_SCREEN_HEIGHT * 4) as usize];
-    let display = Texture2D::from_rgba8(
-        LYNX_SCREEN_WIDTH as u16,
-        LYNX_SCREEN_HEIGHT as u16,
-        rgba_buffer.as_slice(),
-    );
-    display.set_filter(if config.linear_filter() {
-        FilterMode::Linear
-    } else {
-        FilterMode::Nearest
-    });
+/// The cartridge bound parts of the emulation window, rebuilt when another
+/// cartridge is loaded.
+fn build_overlays(
+    config: &RunnerConfig,
+    command_tx: &kanal::Sender<RunnerCommand>,
+    button_mapping: &Rc<RefCell<ButtonMapping>>,
+    hotkey_mapping: &Rc<RefCell<HotkeyMapping>>,
+    chosen_cartridge: &Rc<RefCell<Option<PathBuf>>>,
+) -> Overlays {
+    let cartridge = config.cartridge().as_ref().unwrap();
+    let dir = match cartridge.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    Overlays::new(vec![
+        Box::new(MemoryViewer::new(command_tx.clone())),
+        Box::new(CheatPanel::new(command_tx.clone(), cartridge)),
+        Box::new(SpriteViewer::new(command_tx.clone())),
+        Box::new(MikeyViewer::new(command_tx.clone())),
+        Box::new(BindingsPanel::new(
+            button_mapping.clone(),
+            hotkey_mapping.clone(),
+        )),
+        Box::new(FileBrowser::new(dir, chosen_cartridge.clone())),
+    ])
+}
 
+/// Camera drawing the Lynx screen turned as the cartridge asks, with the
+/// ratio of the turned screen.
+fn camera(rotation: LNXRotation) -> (f32, Camera2D) {
     let (rotation, ratio, zoom) = match rotation {
         LNXRotation::None => (
             0.,
@@ -105,7 +295,7 @@ async fn main() {
             ),
         ),
     };
-    let mut render_target_camera = Camera2D {
+    let render_target_camera = Camera2D {
         target: vec2(
             LYNX_SCREEN_WIDTH as f32 / 2.,
             LYNX_SCREEN_HEIGHT as f32 / 2.,
@@ -116,27 +306,157 @@ async fn main() {
         render_target: Some(render_target(LYNX_SCREEN_WIDTH, LYNX_SCREEN_HEIGHT)),
         viewport: None,
     };
+    (ratio, render_target_camera)
+}
+
+/// The cartridge dropped on the window or picked in the file browser.
+fn next_cartridge(chosen_cartridge: &Rc<RefCell<Option<PathBuf>>>) -> Option<PathBuf> {
+    let dropped = get_dropped_files()
+        .into_iter()
+        .filter_map(|file| file.path)
+        .last();
+    chosen_cartridge.borrow_mut().take().or(dropped)
+}
+
+/// How long the name of a newly loaded cartridge stays on screen, in seconds.
+const LOADED_BANNER_TIME: f64 = 3.;
+
+/// The window can't be renamed once open, the name of a cartridge loaded
+/// later is shown on screen for a while instead.
+fn draw_loaded_banner(banner: &mut Option<(String, f64)>) {
+    let Some((name, until)) = banner.as_ref() else {
+        return;
+    };
+    if get_time() > *until {
+        *banner = None;
+        return;
+    }
+    draw_rectangle(0., 0., screen_width(), 24., Color::new(0., 0., 0., 0.7));
+    draw_text(name, 8., 17., 20., WHITE);
+}
+
+async fn emulate(mut config: RunnerConfig) {
+    let mut runner = Runner::new(config.clone());
+    let (mut input_tx, mut update_display_rx, rotation, command_tx) = runner.initialize_thread();
+    let button_mapping = Rc::new(RefCell::new(config.button_mapping().clone()));
+    let hotkey_mapping = Rc::new(RefCell::new(config.hotkey_mapping().clone()));
+    let chosen_cartridge = Rc::new(RefCell::new(None));
+    let mut hotkey_actions = HotkeyActions::new(command_tx.clone(), &config);
+    let mut overlays = build_overlays(
+        &config,
+        &command_tx,
+        &button_mapping,
+        &hotkey_mapping,
+        &chosen_cartridge,
+    );
+
+    let mut joystick: Joystick = Joystick::empty();
+    let mut switches: Switches = Switches::empty();
+    let mut turbo: Joystick = Joystick::empty();
+
+    let rgba_buffer: Vec<u8> = vec![255; (LYNX_SCREEN_WIDTH * LYNX_SCREEN_HEIGHT * 4) as usize];
+    let display = Texture2D::from_rgba8(
+        LYNX_SCREEN_WIDTH as u16,
+        LYNX_SCREEN_HEIGHT as u16,
+        rgba_buffer.as_slice(),
+    );
+    display.set_filter(if config.linear_filter() {
+        FilterMode::Linear
+    } else {
+        FilterMode::Nearest
+    });
+
+    let (mut ratio, mut render_target_camera) = camera(rotation);
 
     let (mut display_width, mut display_height) = (0., 0.);
     let (mut origin_x, mut origin_y) = (0., 0.);
+    let mut loaded_banner: Option<(String, f64)> = None;
 
     loop {
+        if let Some(cartridge) = next_cartridge(&chosen_cartridge) {
+            // --bank-size describes the startup dump, swapped ones are detected.
+            let loaded = if is_cartridge(&cartridge) {
+                load_lynx(config.rom().as_deref(), &cartridge, None).map(|_| ())
+            } else {
+                Err(format!(
+                    "'{}' isn't a .lnx, .o or .lyx file.",
+                    cartridge.display()
+                ))
+            };
+            match loaded {
+                Ok(()) => {
+                    let name = gamedb::title(&cartridge).unwrap_or_else(|| {
+                        cartridge
+                            .file_name()
+                            .map_or(String::new(), |name| name.to_string_lossy().into_owned())
+                    });
+                    println!("Loading {} ({name}).", cartridge.display());
+                    loaded_banner = Some((name, get_time() + LOADED_BANNER_TIME));
+                    config.set_cartridge(cartridge);
+                    config.set_bank_size(None);
+                    #[cfg(not(feature = "comlynx_external"))]
+                    warn_unlinked(&config);
+                    let (tx, rx, rotation, _) = runner.restart(config.clone());
+                    input_tx = tx;
+                    update_display_rx = rx;
+                    (ratio, render_target_camera) = camera(rotation);
+                    // Forces the render target to be resized.
+                    display_width = 0.;
+                    hotkey_actions = HotkeyActions::new(command_tx.clone(), &config);
+                    overlays = build_overlays(
+                        &config,
+                        &command_tx,
+                        &button_mapping,
+                        &hotkey_mapping,
+                        &chosen_cartridge,
+                    );
+                    joystick = Joystick::empty();
+                    switches = Switches::empty();
+                    turbo = Joystick::empty();
+                }
+                Err(err) => println!("Error: {err}"),
+            }
+        }
+
         let j = joystick;
         let s = switches;
-        config.button_mapping().iter().for_each(|btn| match *btn.1 {
-            Input::Pause => switches.set(Switches::pause, is_key_down(*btn.0)),
-            Input::Up => joystick.set(Joystick::up, is_key_down(*btn.0)),
-            Input::Down => joystick.set(Joystick::down, is_key_down(*btn.0)),
-            Input::Left => joystick.set(Joystick::left, is_key_down(*btn.0)),
-            Input::Right => joystick.set(Joystick::right, is_key_down(*btn.0)),
-            Input::Outside => joystick.set(Joystick::outside, is_key_down(*btn.0)),
-            Input::Inside => joystick.set(Joystick::inside, is_key_down(*btn.0)),
-            Input::Option1 => joystick.set(Joystick::option_1, is_key_down(*btn.0)),
-            Input::Option2 => joystick.set(Joystick::option_2, is_key_down(*btn.0)),
-        });
+        let t = turbo;
+        joystick = Joystick::empty();
+        switches = Switches::empty();
+        turbo = Joystick::empty();
+        let mut triggered: Vec<Hotkey> = vec![];
+        if !overlays.update() {
+            let hotkeys = hotkey_mapping.borrow();
+            let mapping = button_mapping.borrow();
+            let chords = || hotkeys.keys().chain(mapping.keys());
+            for (key, hotkey) in hotkeys.iter() {
+                if key.is_pressed(chords()) {
+                    triggered.push(*hotkey);
+                }
+            }
+            for (key, btn) in mapping.iter() {
+                if !key.is_held(chords()) || hotkeys.contains_key(key) {
+                    continue;
+                }
+                match btn.turbo() {
+                    Some(bit) => turbo |= bit,
+                    None => btn.set(&mut joystick, &mut switches, true),
+                }
+            }
+        }
+        if !triggered
+            .iter()
+            .all(|hotkey| hotkey_actions.trigger(*hotkey))
+        {
+            break;
+        }
+        hotkey_actions.poll();
         if j != joystick || s != switches {
             input_tx.send((joystick.bits(), switches.bits())).unwrap();
         }
+        if t != turbo {
+            let _ = command_tx.send(RunnerCommand::SetTurbo(turbo.bits()));
+        }
 
         let (dw, dh) = screen_size();
         if dw != display_width || dh != display_height {
@@ -164,48 +484,122 @@ async fn main() {
             origin_y,
             WHITE,
         );
+        overlays.draw();
+        draw_loaded_banner(&mut loaded_banner);
 
         next_frame().await
     }
 }
 
-fn process_args() -> RunnerConfig {
-    let args = Args::parse();
-
+fn process_args(args: Args) -> RunnerConfig {
     let mut config = RunnerConfig::new();
     if let Some(rom) = args.rom {
         config.set_rom(rom);
     }
-    config.set_cartridge(args.cartridge);
+    config.set_cartridge(args.cartridge.unwrap());
+    config.set_bank_size(args.bank_size);
 
     config.set_linear_filter(args.linear);
+    config.set_turbo_rate(args.turbo_rate);
+    config.set_reset_after(args.reset_after);
     config.set_mute(args.mute);
+    config.set_debugger(args.debug);
+    config.set_gdb_port(args.gdb);
+    if let Some(path) = args.symbols {
+        config.set_symbols(Symbols::load(&path).unwrap_or_else(|err| panic!("{err}")));
+    }
+    if let Some(path) = args.trace {
+        config.set_trace(Some(TraceConfig::new(
+            path,
+            args.trace_range,
+            args.trace_frames,
+        )));
+    }
+    config.set_script(args.script);
+    config.set_control(args.control);
     #[cfg(not(feature = "comlynx_external"))]
     config.set_comlynx(args.comlynx);
     #[cfg(feature = "comlynx_external")]
-    config.set_comlynx_port(args.comlynx);
-
-    let btns = args.buttons.unwrap();
-    if btns.len() != 9 {
-        panic!("Buttons mapping should be 9 keys.");
-    }
-    for (s, btn) in btns.iter().zip([
-        Input::Up,
-        Input::Down,
-        Input::Left,
-        Input::Right,
-        Input::Outside,
-        Input::Inside,
-        Input::Option1,
-        Input::Option2,
-        Input::Pause,
-    ]) {
-        let key = translate_keycode(s);
-        if key == KeyCode::Unknown {
-            panic!("Buttons mapping: Unknown key '{}'.", s.as_str());
+    if let Some(endpoint) = args.comlynx {
+        config.set_comlynx_endpoint(endpoint);
+    }
+    #[cfg(feature = "comlynx_external")]
+    config.set_comlynx_raw(args.comlynx_raw);
+    #[cfg(feature = "comlynx_external")]
+    if let Some(window) = args.comlynx_lockstep {
+        if args.comlynx_raw {
+            panic!("Comlynx lockstep requires the framed protocol, it can't be used with --comlynx-raw.");
+        }
+        if matches!(config.comlynx_endpoint(), ComlynxEndpoint::Udp { .. }) {
+            panic!("Comlynx lockstep requires a reliable transport, use tcp or unix.");
+        }
+        if window == 0 {
+            panic!("Comlynx lockstep window must be at least 1 cycle.");
+        }
+        config.set_comlynx_lockstep(Some(window));
+    }
+    #[cfg(feature = "comlynx_external")]
+    if let Some(port) = args.netplay {
+        config.set_netplay(Some(NetplayConfig::new(
+            port,
+            args.netplay_peer.unwrap(),
+            args.netplay_player - 1,
+            args.netplay_delay,
+        )));
+    }
+
+    let mut mapping = ButtonMapping::new();
+    for (s, btn) in DEFAULT_BUTTONS.split(',').zip(Input::ALL) {
+        mapping.insert(KeyChord::new(translate_keycode(s)), btn);
+    }
+    let mut hotkeys = Hotkey::defaults();
+    if let Some(path) = bindings::path() {
+        match bindings::load(&path) {
+            Ok(entries) => {
+                for entry in entries {
+                    let result = match entry.strip_prefix(bindings::HOTKEY_PREFIX) {
+                        Some(entry) => bindings::apply(&mut hotkeys, entry),
+                        None => bindings::apply(&mut mapping, &entry),
+                    };
+                    if let Err(err) = result {
+                        println!("Bindings file {}: {err}", path.display());
+                    }
+                }
+            }
+            Err(err) => println!("Bindings not loaded: {err}"),
         }
+    }
+    for h in args.hotkeys.unwrap_or_default() {
+        bindings::apply(&mut hotkeys, &h).unwrap_or_else(|err| panic!("Hotkeys mapping: {err}"));
+    }
+    if let Some(btns) = args.buttons {
+        if btns.iter().any(|b| b.contains('=')) {
+            for b in &btns {
+                bindings::apply(&mut mapping, b)
+                    .unwrap_or_else(|err| panic!("Buttons mapping: {err}"));
+            }
+        } else {
+            if btns.len() != 9 {
+                panic!("Buttons mapping should be 9 keys.");
+            }
+            for (s, btn) in btns.iter().zip(Input::ALL) {
+                let key = translate_keycode(s);
+                if key == KeyCode::Unknown {
+                    panic!("Buttons mapping: Unknown key '{}'.", s.as_str());
+                }
+                mapping.retain(|_, b| *b != btn);
+                mapping.insert(KeyChord::new(key), btn);
+            }
+        }
+    }
+    for (key, btn) in mapping {
+        debug!("Key '{key}' mapped to {btn:?}.");
         config.set_button_mapping(key, btn);
     }
+    for (key, hotkey) in &hotkeys {
+        debug!("Key '{key}' bound to the {} hotkey.", hotkey.name());
+    }
+    config.set_hotkey_mapping(hotkeys);
 
     config
 }
@@ -0,0 +1,299 @@
+use crate::{overlay::Overlay, runner::RunnerCommand};
+use macroquad::prelude::*;
+
+const TOGGLE_KEY: KeyCode = KeyCode::F2;
+const BYTES_PER_ROW: u16 = 16;
+const FONT_SIZE: u16 = 16;
+const LINE_HEIGHT: f32 = 16.;
+const MARGIN: f32 = 8.;
+/// Number of refreshes a changed byte stays highlighted.
+const HIGHLIGHT_REFRESHES: u32 = 60;
+const HELP: &str = "F2 close  arrows/PgUp/PgDn/wheel move  0-F edit  G goto";
+
+fn region(addr: u16) -> &'static str {
+    match addr {
+        0x0000..=0xFBFF => "RAM",
+        0xFC00..=0xFCFF => "Suzy",
+        0xFD00..=0xFDFF => "Mikey",
+        0xFE00..=0xFFF7 => "ROM",
+        0xFFF8..=0xFFF9 => "MAPCTL",
+        0xFFFA..=0xFFFF => "Vectors",
+    }
+}
+
+/// Hex dump of the 64K address space drawn over the emulation, refreshed
+/// every frame while visible. Bytes can be edited in place; the ones that
+/// changed recently are highlighted.
+pub(crate) struct MemoryViewer {
+    command_tx: kanal::Sender<RunnerCommand>,
+    memory_tx: kanal::Sender<Vec<u8>>,
+    memory_rx: kanal::Receiver<Vec<u8>>,
+    visible: bool,
+    /// First address of the rows requested from the runner.
+    requested: Option<u16>,
+    data: Vec<u8>,
+    /// Bytes read at least once, the others can't be told changed.
+    loaded: Vec<bool>,
+    changed: Vec<u32>,
+    refresh: u32,
+    top: u16,
+    cursor: u16,
+    nibble: Option<u8>,
+    goto: Option<String>,
+}
+
+impl MemoryViewer {
+    pub(crate) fn new(command_tx: kanal::Sender<RunnerCommand>) -> Self {
+        let (memory_tx, memory_rx) = kanal::bounded::<Vec<u8>>(1);
+        Self {
+            command_tx,
+            memory_tx,
+            memory_rx,
+            visible: false,
+            requested: None,
+            data: vec![0; 0x10000],
+            loaded: vec![false; 0x10000],
+            changed: vec![0; 0x10000],
+            refresh: HIGHLIGHT_REFRESHES,
+            top: 0,
+            cursor: 0,
+            nibble: None,
+            goto: None,
+        }
+    }
+
+    fn rows() -> u16 {
+        let rows = ((screen_height() - MARGIN * 2.) / LINE_HEIGHT).max(2.) as u16;
+        rows - 1
+    }
+
+    fn char_width() -> f32 {
+        measure_text("0", None, FONT_SIZE, 1.).width
+    }
+
+    fn keys(&mut self) {
+        let page = Self::rows() * BYTES_PER_ROW;
+        let moves = [
+            (KeyCode::Left, 1u16.wrapping_neg()),
+            (KeyCode::Right, 1),
+            (KeyCode::Up, BYTES_PER_ROW.wrapping_neg()),
+            (KeyCode::Down, BYTES_PER_ROW),
+            (KeyCode::PageUp, page.wrapping_neg()),
+            (KeyCode::PageDown, page),
+        ];
+        for (key, offset) in moves {
+            if is_key_pressed(key) {
+                self.move_to(self.cursor.wrapping_add(offset));
+            }
+        }
+
+        while let Some(c) = get_char_pressed() {
+            if let Some(goto) = self.goto.as_mut() {
+                if c.is_ascii_hexdigit() && goto.len() < 4 {
+                    goto.push(c.to_ascii_uppercase());
+                }
+                continue;
+            }
+            if c == 'g' || c == 'G' {
+                self.goto = Some(String::new());
+                self.nibble = None;
+                continue;
+            }
+            let Some(digit) = c.to_digit(16).and_then(|d| u8::try_from(d).ok()) else {
+                continue;
+            };
+            match self.nibble.take() {
+                None => self.nibble = Some(digit),
+                Some(high) => {
+                    let byte = (high << 4) | digit;
+                    let _ = self.command_tx.send(RunnerCommand::Poke(self.cursor, byte));
+                    self.data[usize::from(self.cursor)] = byte;
+                    self.move_to(self.cursor.wrapping_add(1));
+                }
+            }
+        }
+
+        if is_key_pressed(KeyCode::Escape) {
+            self.goto = None;
+            self.nibble = None;
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            if let Some(addr) = self
+                .goto
+                .take()
+                .and_then(|goto| u16::from_str_radix(&goto, 16).ok())
+            {
+                self.move_to(addr);
+            }
+        }
+    }
+
+    fn mouse(&mut self) {
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0. {
+            let rows = if wheel > 0. {
+                BYTES_PER_ROW.wrapping_neg()
+            } else {
+                BYTES_PER_ROW
+            };
+            self.top = self.top.wrapping_add(rows.wrapping_mul(3));
+        }
+
+        if !is_mouse_button_pressed(MouseButton::Left) {
+            return;
+        }
+        let (x, y) = mouse_position();
+        let column = ((x - MARGIN) / Self::char_width() - 6.) / 3.;
+        let row = (y - MARGIN) / LINE_HEIGHT - 1.;
+        if (0. ..16.).contains(&column) && (0. ..f32::from(Self::rows())).contains(&row) {
+            let offset = row as u16 * BYTES_PER_ROW + column as u16;
+            self.nibble = None;
+            self.cursor = self.top.wrapping_add(offset);
+        }
+    }
+
+    fn move_to(&mut self, addr: u16) {
+        self.cursor = addr;
+        self.nibble = None;
+        let page = Self::rows() * BYTES_PER_ROW;
+        if addr.wrapping_sub(self.top) >= page {
+            self.top = (addr / BYTES_PER_ROW * BYTES_PER_ROW)
+                .wrapping_sub(page / 2 / BYTES_PER_ROW * BYTES_PER_ROW);
+        }
+    }
+}
+
+impl Overlay for MemoryViewer {
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn update(&mut self) -> bool {
+        if is_key_pressed(TOGGLE_KEY) {
+            self.visible = !self.visible;
+            self.nibble = None;
+            self.goto = None;
+        }
+        if !self.visible {
+            return false;
+        }
+
+        if let Ok(Some(data)) = self.memory_rx.try_recv() {
+            self.refresh += 1;
+            let start = self.requested.take().unwrap_or_default();
+            for (addr, new) in (0..=u16::MAX).map(|i| start.wrapping_add(i)).zip(data) {
+                let i = usize::from(addr);
+                if self.loaded[i] && self.data[i] != new {
+                    self.changed[i] = self.refresh;
+                }
+                self.data[i] = new;
+                self.loaded[i] = true;
+            }
+        }
+        if self.requested.is_none() {
+            // Only the rows shown, the runner reads them between two ticks.
+            let len = usize::from(Self::rows()) * usize::from(BYTES_PER_ROW);
+            let sent = self
+                .command_tx
+                .send(RunnerCommand::ReadMemory(
+                    self.top,
+                    len,
+                    self.memory_tx.clone(),
+                ))
+                .is_ok();
+            self.requested = sent.then_some(self.top);
+        }
+
+        self.keys();
+        self.mouse();
+        true
+    }
+
+    fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        let char_width = Self::char_width();
+        draw_rectangle(
+            0.,
+            0.,
+            screen_width(),
+            screen_height(),
+            Color::new(0., 0., 0., 0.85),
+        );
+
+        let header = match (&self.goto, self.nibble) {
+            (Some(goto), _) => format!("Goto ${goto}_"),
+            (None, Some(high)) => {
+                format!("${:04X}  {}  {high:X}_", self.cursor, region(self.cursor))
+            }
+            (None, None) => format!(
+                "${:04X}  {}  ${:02X}",
+                self.cursor,
+                region(self.cursor),
+                self.data[usize::from(self.cursor)]
+            ),
+        };
+        let text_y = |row: f32| MARGIN + LINE_HEIGHT * (row + 1.) - 4.;
+        draw_text(
+            &format!("{header}    {HELP}"),
+            MARGIN,
+            text_y(0.),
+            f32::from(FONT_SIZE),
+            WHITE,
+        );
+
+        for row in 0..Self::rows() {
+            let row_addr = self.top.wrapping_add(row * BYTES_PER_ROW);
+            let y = text_y(f32::from(row + 1));
+            draw_text(
+                &format!("{row_addr:04X}"),
+                MARGIN,
+                y,
+                f32::from(FONT_SIZE),
+                GRAY,
+            );
+
+            for column in 0..BYTES_PER_ROW {
+                let addr = row_addr.wrapping_add(column);
+                let byte = self.data[usize::from(addr)];
+                let x = MARGIN + char_width * (6. + 3. * f32::from(column));
+                let mut color =
+                    if self.refresh - self.changed[usize::from(addr)] < HIGHLIGHT_REFRESHES {
+                        RED
+                    } else {
+                        WHITE
+                    };
+                if addr == self.cursor {
+                    draw_rectangle(
+                        x - 1.,
+                        y - LINE_HEIGHT + 4.,
+                        char_width * 2. + 2.,
+                        LINE_HEIGHT,
+                        YELLOW,
+                    );
+                    color = BLACK;
+                }
+                draw_text(&format!("{byte:02X}"), x, y, f32::from(FONT_SIZE), color);
+            }
+
+            let ascii: String = (0..BYTES_PER_ROW)
+                .map(|column| {
+                    let byte = self.data[usize::from(row_addr.wrapping_add(column))];
+                    if byte.is_ascii_graphic() {
+                        char::from(byte)
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            draw_text(
+                &ascii,
+                MARGIN + char_width * (7. + 3. * f32::from(BYTES_PER_ROW)),
+                y,
+                f32::from(FONT_SIZE),
+                GRAY,
+            );
+        }
+    }
+}
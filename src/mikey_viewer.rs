@@ -0,0 +1,166 @@
+use crate::{
+    debugger::mikey::{counter_name, Counter, Mikey, MIKEY_BASE},
+    overlay::Overlay,
+    runner::RunnerCommand,
+};
+use macroquad::prelude::*;
+
+const TOGGLE_KEY: KeyCode = KeyCode::F5;
+const FONT_SIZE: f32 = 16.;
+const LINE_HEIGHT: f32 = 16.;
+const MARGIN: f32 = 8.;
+
+fn flag(set: bool, name: &str) -> String {
+    if set {
+        name.to_string()
+    } else {
+        "-".repeat(name.len())
+    }
+}
+
+fn counter_columns(counter: &Counter, source: Option<usize>) -> String {
+    let clock = match source {
+        Some(source) => format!("<{}", counter_name(source)),
+        None => counter.clock().to_string(),
+    };
+    format!(
+        "{:02X}  {:02X}  {:<6} {} {} {}",
+        counter.backup,
+        counter.count,
+        clock,
+        flag(counter.enabled(), "cnt"),
+        flag(counter.reload(), "rld"),
+        flag(counter.done(), "done"),
+    )
+}
+
+/// Live Mikey timers, audio channels and interrupts, refreshed every frame.
+pub(crate) struct MikeyViewer {
+    command_tx: kanal::Sender<RunnerCommand>,
+    memory_tx: kanal::Sender<Vec<u8>>,
+    memory_rx: kanal::Receiver<Vec<u8>>,
+    visible: bool,
+    requested: bool,
+    mikey: Option<Mikey>,
+}
+
+impl MikeyViewer {
+    pub(crate) fn new(command_tx: kanal::Sender<RunnerCommand>) -> Self {
+        let (memory_tx, memory_rx) = kanal::bounded::<Vec<u8>>(1);
+        Self {
+            command_tx,
+            memory_tx,
+            memory_rx,
+            visible: false,
+            requested: false,
+            mikey: None,
+        }
+    }
+}
+
+impl Overlay for MikeyViewer {
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn update(&mut self) -> bool {
+        if is_key_pressed(TOGGLE_KEY) {
+            self.visible = !self.visible;
+        }
+        if !self.visible {
+            return false;
+        }
+
+        if let Ok(Some(memory)) = self.memory_rx.try_recv() {
+            self.mikey = Some(Mikey::decode(&memory));
+            self.requested = false;
+        }
+        if !self.requested {
+            self.requested = self
+                .command_tx
+                .send(RunnerCommand::ReadMemory(
+                    MIKEY_BASE,
+                    0x100,
+                    self.memory_tx.clone(),
+                ))
+                .is_ok();
+        }
+        true
+    }
+
+    fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        draw_rectangle(
+            0.,
+            0.,
+            screen_width(),
+            screen_height(),
+            Color::new(0., 0., 0., 0.85),
+        );
+        let mut row = 0.;
+        let mut line = |text: &str, color: Color| {
+            row += 1.;
+            draw_text(
+                text,
+                MARGIN,
+                MARGIN + LINE_HEIGHT * row - 4.,
+                FONT_SIZE,
+                color,
+            );
+        };
+
+        line("F5 close", WHITE);
+        let Some(mikey) = &self.mikey else {
+            return;
+        };
+
+        line("", WHITE);
+        line("Timer  bkp cnt clock  flags        irq pending", GRAY);
+        for (i, timer) in mikey.timers.iter().enumerate() {
+            let pending = mikey.pending & (1 << i) != 0;
+            line(
+                &format!(
+                    "{:<6} {}  {}  {}",
+                    counter_name(i),
+                    counter_columns(&timer.counter, timer.source),
+                    flag(timer.interrupt_enabled(), "on"),
+                    flag(pending, "yes"),
+                ),
+                if pending { YELLOW } else { WHITE },
+            );
+        }
+
+        line("", WHITE);
+        line(
+            "Audio  bkp cnt clock  flags        vol  out  shift taps         int atten",
+            GRAY,
+        );
+        for (i, channel) in mikey.audio.iter().enumerate() {
+            line(
+                &format!(
+                    "{:<6} {}  {:4} {:4}  {:03X}  {:012b} {}  {:02X}",
+                    counter_name(8 + i),
+                    counter_columns(&channel.counter, channel.source),
+                    channel.volume,
+                    channel.output,
+                    channel.shift,
+                    channel.taps,
+                    flag(channel.integrate, "int"),
+                    channel.attenuation,
+                ),
+                WHITE,
+            );
+        }
+
+        line("", WHITE);
+        line(
+            &format!(
+                "Interrupts pending {:08b}  Panning {:02X}  Stereo {:02X}",
+                mikey.pending, mikey.panning, mikey.stereo
+            ),
+            WHITE,
+        );
+    }
+}
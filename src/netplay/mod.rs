@@ -0,0 +1,43 @@
+#[cfg(feature = "comlynx_external")]
+pub(crate) mod netplay_runner_thread;
+#[cfg(feature = "comlynx_external")]
+pub(crate) mod packet;
+pub(crate) mod proxy;
+
+#[cfg(feature = "comlynx_external")]
+#[derive(Clone)]
+pub(crate) struct NetplayConfig {
+    port: u16,
+    peer: String,
+    player: u8,
+    delay: u8,
+}
+
+#[cfg(feature = "comlynx_external")]
+impl NetplayConfig {
+    pub(crate) fn new(port: u16, peer: String, player: u8, delay: u8) -> Self {
+        Self {
+            port,
+            peer,
+            player,
+            delay,
+        }
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub(crate) fn peer(&self) -> &str {
+        &self.peer
+    }
+
+    /// Local player, 0 or 1.
+    pub(crate) fn player(&self) -> u8 {
+        self.player
+    }
+
+    pub(crate) fn delay(&self) -> u8 {
+        self.delay
+    }
+}
@@ -0,0 +1,402 @@
+use super::packet::{InputPacket, MAX_INPUTS};
+use crate::{
+    debugger::cpu,
+    gamedb,
+    runner::{fnv1a, RunnerCommand, RunnerConfig, RunnerThread, SAMPLE_RATE, SAMPLE_TICKS},
+    sound_source::SoundSource,
+};
+use holani::{cartridge::lnx_header::LNXRotation, lynx::Lynx};
+use log::trace;
+use ringbuf::{
+    traits::{Producer, Split as _},
+    HeapProd, HeapRb,
+};
+use rodio::OutputStream;
+use std::{
+    collections::VecDeque,
+    io::ErrorKind,
+    net::UdpSocket,
+    time::{Duration, Instant},
+};
+
+pub(crate) const MAX_ROLLBACK: u32 = 12;
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Rollback netplay between two peers.
+///
+/// Both peers emulate the two consoles of a link-cable game, wired together
+/// in-process through their external Comlynx ports, and only exchange
+/// controller inputs. The remote player is assumed to keep holding its last
+/// known input; when the actual input turns out different, both consoles are
+/// restored from the snapshot taken before that frame and re-simulated.
+///
+/// A frame ends when the first console redraws its screen, on both peers
+/// whichever player they are, so that they cut the inputs at the same cycle.
+///
+/// Runner commands that would make the consoles diverge from the peer's are
+/// refused, only the read-only ones, the buttons and the mute are served.
+pub(crate) struct NetplayRunnerThread {
+    consoles: [Lynx; 2],
+    config: RunnerConfig,
+    input_rx: kanal::Receiver<(u8, u8)>,
+    update_display_tx: kanal::Sender<Vec<u8>>,
+    rotation_tx: kanal::Sender<LNXRotation>,
+    command_rx: kanal::Receiver<RunnerCommand>,
+    socket: Option<UdpSocket>,
+    cart_hash: u64,
+    player: usize,
+    delay: u8,
+    frame: u32,
+    current_input: (u8, u8),
+    local_inputs: Vec<(u8, u8)>,
+    remote_inputs: Vec<(u8, u8)>,
+    used_remote_inputs: Vec<(u8, u8)>,
+    peer_ack: u32,
+    rollback_to: Option<u32>,
+    snapshots: VecDeque<(u32, [Lynx; 2])>,
+    connected: bool,
+    last_packet: Instant,
+    frame_time: Duration,
+    next_frame: Instant,
+    last_refresh_rate: f64,
+    muted: bool,
+    sound_tick: u32,
+    stream: Option<OutputStream>,
+}
+
+impl NetplayRunnerThread {
+    pub(crate) fn new(
+        config: RunnerConfig,
+        input_rx: kanal::Receiver<(u8, u8)>,
+        update_display_tx: kanal::Sender<Vec<u8>>,
+        rotation_tx: kanal::Sender<LNXRotation>,
+        command_rx: kanal::Receiver<RunnerCommand>,
+    ) -> Self {
+        let netplay = config.netplay().clone().unwrap();
+        // Nobody presses anything during the first frames of input delay.
+        let neutral = vec![(0, 0); usize::from(netplay.delay())];
+        Self {
+            consoles: [Lynx::new(), Lynx::new()],
+            input_rx,
+            update_display_tx,
+            rotation_tx,
+            command_rx,
+            socket: None,
+            cart_hash: 0,
+            player: netplay.player() as usize,
+            delay: netplay.delay(),
+            frame: 0,
+            current_input: (0, 0),
+            local_inputs: neutral.clone(),
+            remote_inputs: neutral.clone(),
+            used_remote_inputs: neutral,
+            peer_ack: 0,
+            rollback_to: None,
+            snapshots: VecDeque::new(),
+            connected: false,
+            last_packet: Instant::now(),
+            frame_time: Duration::from_millis(16),
+            next_frame: Instant::now(),
+            last_refresh_rate: 0f64,
+            muted: config.mute(),
+            sound_tick: 0,
+            stream: None,
+            config,
+        }
+    }
+
+    fn inputs(&mut self) -> bool {
+        if self.input_rx.is_disconnected() {
+            return true;
+        } else if let Ok(Some(input)) = self.input_rx.try_recv() {
+            self.current_input = input;
+        }
+        while let Ok(Some(command)) = self.command_rx.try_recv() {
+            self.command(command);
+        }
+        false
+    }
+
+    fn command(&mut self, command: RunnerCommand) {
+        let refused = "Not available during netplay.".to_string();
+        match command {
+            RunnerCommand::ReadMemory(addr, len, reply_tx) => {
+                let _ = reply_tx.try_send(cpu::peek_range(&self.consoles[self.player], addr, len));
+            }
+            RunnerCommand::ReadScreen(reply_tx) => {
+                let _ = reply_tx.try_send(self.consoles[self.player].screen_rgba().clone());
+            }
+            RunnerCommand::SetButtons(joystick, switches) => {
+                self.current_input = (joystick, switches);
+            }
+            RunnerCommand::SetMute(muted) => self.muted = muted,
+            RunnerCommand::SaveState(_, reply_tx)
+            | RunnerCommand::LoadState(_, reply_tx)
+            | RunnerCommand::Reset(reply_tx)
+            | RunnerCommand::PowerCycle(reply_tx) => {
+                let _ = reply_tx.try_send(Err(refused));
+            }
+            // Dropping the reply senders cancels the requests waiting on them.
+            RunnerCommand::CaptureSprites(_)
+            | RunnerCommand::StepFrames(..)
+            | RunnerCommand::Poke(..)
+            | RunnerCommand::SetCheats(_)
+            | RunnerCommand::Pause(_)
+            | RunnerCommand::TogglePause
+            | RunnerCommand::SetTurbo(_) => println!("Netplay: {refused}"),
+        }
+    }
+
+    fn receive(&mut self) {
+        let mut datagram = [0; 512];
+        loop {
+            let len = match self.socket.as_ref().unwrap().recv(&mut datagram) {
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => break,
+                Err(e) => {
+                    eprintln!("Netplay: {e}");
+                    break;
+                }
+            };
+            let Some(packet) = InputPacket::decode(&datagram[..len]) else {
+                continue;
+            };
+            if packet.cart_hash != self.cart_hash || packet.delay != self.delay {
+                if !self.connected {
+                    println!("Netplay: ignoring peer running another cartridge or input delay.");
+                }
+                continue;
+            }
+            if !self.connected {
+                println!("Netplay: peer connected.");
+                self.connected = true;
+            }
+            self.last_packet = Instant::now();
+            // Nothing past what was sent can be acknowledged.
+            let sent = self.local_inputs.len() as u32;
+            self.peer_ack = self.peer_ack.max(packet.ack.min(sent));
+
+            let mut frame = packet.first_frame;
+            for input in packet.inputs {
+                if frame as usize == self.remote_inputs.len() {
+                    self.remote_inputs.push(input);
+                    if frame < self.frame && self.used_remote_inputs[frame as usize] != input {
+                        self.rollback_to = Some(self.rollback_to.map_or(frame, |f| f.min(frame)));
+                    }
+                }
+                frame = frame.wrapping_add(1);
+            }
+        }
+
+        if self.connected && self.last_packet.elapsed() > PEER_TIMEOUT {
+            println!("Netplay: peer timed out, waiting for it.");
+            self.connected = false;
+        }
+    }
+
+    fn send(&mut self) {
+        let first = self.peer_ack.max(u32::from(self.delay));
+        let last = (self.local_inputs.len() as u32).min(first + MAX_INPUTS as u32);
+        let packet = InputPacket {
+            cart_hash: self.cart_hash,
+            delay: self.delay,
+            ack: self.remote_inputs.len() as u32,
+            first_frame: first,
+            inputs: self.local_inputs[first as usize..last as usize].to_vec(),
+        };
+        let _ = self.socket.as_ref().unwrap().send(&packet.encode());
+    }
+
+    fn remote_input(&self, frame: u32) -> (u8, u8) {
+        self.remote_inputs
+            .get(frame as usize)
+            .or(self.remote_inputs.last())
+            .copied()
+            .unwrap_or((0, 0))
+    }
+
+    fn save_snapshot(&mut self, frame: u32) {
+        while self.snapshots.back().is_some_and(|(f, _)| *f >= frame) {
+            self.snapshots.pop_back();
+        }
+        if self.snapshots.len() > MAX_ROLLBACK as usize {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((frame, self.consoles.clone()));
+    }
+
+    fn simulate_frame(&mut self, frame: u32, sound_buffer: Option<&mut HeapProd<i16>>) {
+        let local = self.local_inputs[frame as usize];
+        let remote = self.remote_input(frame);
+        if let Some(used) = self.used_remote_inputs.get_mut(frame as usize) {
+            *used = remote;
+        } else {
+            self.used_remote_inputs.push(remote);
+        }
+
+        let (p1, p2) = if self.player == 0 {
+            (local, remote)
+        } else {
+            (remote, local)
+        };
+        for (console, (joy, sw)) in self.consoles.iter_mut().zip([p1, p2]) {
+            console.set_joystick_u8(joy);
+            console.set_switches_u8(sw);
+        }
+
+        let mut sound_buffer = sound_buffer;
+        loop {
+            self.consoles[0].tick();
+            self.consoles[1].tick();
+            if let Some(data) = self.consoles[0].comlynx_ext_tx() {
+                self.consoles[1].comlynx_ext_rx(data);
+            }
+            if let Some(data) = self.consoles[1].comlynx_ext_tx() {
+                self.consoles[0].comlynx_ext_rx(data);
+            }
+            if let Some(buffer) = sound_buffer.as_deref_mut() {
+                self.sound(buffer);
+            }
+            if self.consoles[0].redraw_requested() {
+                break;
+            }
+        }
+    }
+
+    fn sound(&mut self, prod: &mut HeapProd<i16>) {
+        if self.muted {
+            return;
+        }
+
+        self.sound_tick += 1;
+        if self.sound_tick < SAMPLE_TICKS {
+            return;
+        }
+
+        self.sound_tick = 0;
+        let (l, r) = self.consoles[self.player].audio_sample();
+        prod.push_slice(&[l, r]);
+    }
+
+    fn rollback(&mut self, to: u32) {
+        let Some(index) = self.snapshots.iter().position(|(f, _)| *f == to) else {
+            eprintln!("Netplay: frame {to} is too old to roll back, desynchronised.");
+            return;
+        };
+        trace!("Netplay: rolling back {} frame(s).", self.frame - to);
+        self.consoles = self.snapshots[index].1.clone();
+        for frame in to..self.frame {
+            self.save_snapshot(frame);
+            self.simulate_frame(frame, None);
+        }
+    }
+
+    fn display(&mut self) {
+        let screen = self.consoles[self.player].screen_rgba().clone();
+        let _ = self.update_display_tx.try_send(screen);
+    }
+}
+
+impl RunnerThread for NetplayRunnerThread {
+    fn initialize(&mut self) -> Result<(), &str> {
+        if let Some(rom) = self.config.rom() {
+            let Ok(data) = std::fs::read(rom) else {
+                return Err("Couldn't not load ROM file.");
+            };
+            for console in &mut self.consoles {
+                if console.load_rom_from_slice(&data).is_err() {
+                    return Err("Couldn't not load ROM file.");
+                }
+            }
+            trace!("ROM loaded.");
+        }
+
+        match self.config.cartridge() {
+            None => panic!("A cartridge is required."),
+            Some(cart) => {
+                let Ok(data) = gamedb::read_cartridge(cart, self.config.bank_size()) else {
+                    return Err("Couldn't not load Cartridge file.");
+                };
+                for console in &mut self.consoles {
+                    if console.load_cart_from_slice(&data).is_err() {
+                        return Err("Couldn't not load Cartridge file.");
+                    }
+                    console.set_comlynx_cable_present(true);
+                }
+                self.cart_hash = fnv1a(&data);
+            }
+        }
+
+        trace!("Cart loaded.");
+
+        let netplay = self.config.netplay().clone().unwrap();
+        let Ok(socket) = UdpSocket::bind(format!("0.0.0.0:{}", netplay.port())) else {
+            return Err("Couldn't not bind the netplay socket.");
+        };
+        if socket.connect(netplay.peer()).is_err() || socket.set_nonblocking(true).is_err() {
+            return Err("Couldn't not reach the netplay peer.");
+        }
+        self.socket = Some(socket);
+        println!(
+            "Netplay: player {} on port {}, waiting for {}.",
+            self.player + 1,
+            netplay.port(),
+            netplay.peer()
+        );
+
+        self.rotation_tx.send(self.consoles[0].rotation()).unwrap();
+
+        Ok(())
+    }
+
+    fn run(&mut self) {
+        let sound_ringbuf = HeapRb::<i16>::new(SAMPLE_RATE as usize * 2);
+        let (mut sound_buffer, sound_consumer) = sound_ringbuf.split();
+
+        if !self.config.mute() {
+            let stream_handle = rodio::OutputStreamBuilder::open_default_stream()
+                .expect("open default audio stream");
+            let source = SoundSource::new(sound_consumer);
+            stream_handle.mixer().add(source);
+            self.stream = Some(stream_handle);
+        }
+
+        loop {
+            if self.inputs() {
+                return;
+            }
+
+            self.receive();
+            if let Some(to) = self.rollback_to.take() {
+                self.rollback(to);
+            }
+
+            // Don't run further ahead of the peer than what can be rolled back.
+            let confirmed = self.remote_inputs.len() as u32;
+            if !self.connected || self.frame >= confirmed + MAX_ROLLBACK {
+                self.send();
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+
+            self.local_inputs.push(self.current_input);
+            self.save_snapshot(self.frame);
+            self.simulate_frame(self.frame, Some(&mut sound_buffer));
+            self.frame += 1;
+            self.send();
+            self.display();
+
+            let rf = self.consoles[0].display_refresh_rate();
+            if rf != self.last_refresh_rate {
+                self.last_refresh_rate = rf;
+                self.frame_time =
+                    Duration::from_micros((1000000f64 / self.last_refresh_rate) as u64);
+                trace!("set refresh rate to {} ({:?})", rf, self.frame_time);
+            }
+
+            while self.next_frame > Instant::now() {}
+            self.next_frame = Instant::now() + self.frame_time;
+        }
+    }
+}
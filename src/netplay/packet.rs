@@ -0,0 +1,54 @@
+const MAGIC: [u8; 4] = *b"HLNP";
+const HEADER_LEN: usize = 22;
+pub const MAX_INPUTS: usize = 64;
+
+/// Datagram exchanged between netplay peers.
+///
+/// Carries the sender's inputs for `first_frame..first_frame + inputs.len()`,
+/// all the frames the receiver hasn't acknowledged yet so lost datagrams are
+/// recovered by the next one, and `ack`, the number of consecutive frames of
+/// the receiver's inputs the sender already has.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct InputPacket {
+    pub cart_hash: u64,
+    pub delay: u8,
+    pub ack: u32,
+    pub first_frame: u32,
+    pub inputs: Vec<(u8, u8)>,
+}
+
+impl InputPacket {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.inputs.len() * 2);
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&self.cart_hash.to_le_bytes());
+        out.push(self.delay);
+        out.extend_from_slice(&self.ack.to_le_bytes());
+        out.extend_from_slice(&self.first_frame.to_le_bytes());
+        out.push(u8::try_from(self.inputs.len()).unwrap());
+        for (joy, sw) in &self.inputs {
+            out.extend_from_slice(&[*joy, *sw]);
+        }
+        out
+    }
+
+    pub(crate) fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_LEN || data[0..4] != MAGIC {
+            return None;
+        }
+        let count = data[21] as usize;
+        if data.len() != HEADER_LEN + count * 2 {
+            return None;
+        }
+        Some(Self {
+            cart_hash: u64::from_le_bytes(data[4..12].try_into().unwrap()),
+            delay: data[12],
+            ack: u32::from_le_bytes(data[13..17].try_into().unwrap()),
+            first_frame: u32::from_le_bytes(data[17..21].try_into().unwrap()),
+            inputs: data[HEADER_LEN..]
+                .chunks_exact(2)
+                .map(|c| (c[0], c[1]))
+                .collect(),
+        })
+    }
+}
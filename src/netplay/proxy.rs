@@ -0,0 +1,117 @@
+use clap::Args;
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Args)]
+pub(crate) struct ProxyArgs {
+    /// Local UDP port the first peer sends to
+    #[arg(long, value_name = "PORT")]
+    listen: u16,
+
+    /// Address of the second peer
+    #[arg(long, value_name = "HOST:PORT")]
+    forward: SocketAddr,
+
+    /// Added one-way latency in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    delay: u64,
+
+    /// Random extra latency in milliseconds, up to
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    jitter: u64,
+
+    /// Dropped datagrams, in percent
+    #[arg(long, value_name = "PERCENT", default_value_t = 0)]
+    loss: u8,
+
+    /// Duplicated datagrams, in percent
+    #[arg(long, value_name = "PERCENT", default_value_t = 0)]
+    duplicate: u8,
+}
+
+/// xorshift64*, good enough to pick which datagrams to mess with.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, max: u64) -> u64 {
+        if max == 0 {
+            0
+        } else {
+            self.next() % max
+        }
+    }
+}
+
+/// Relays datagrams between the first peer to send to `--listen` and the
+/// `--forward` peer, impaired as configured.
+pub(crate) fn run(args: &ProxyArgs) {
+    let socket = UdpSocket::bind(format!("0.0.0.0:{}", args.listen))
+        .expect("Couldn't bind the proxy socket.");
+    socket
+        .set_read_timeout(Some(Duration::from_millis(1)))
+        .unwrap();
+    println!(
+        "Netplay proxy on port {}, forwarding to {} (delay {}ms, jitter {}ms, loss {}%, duplicate {}%)",
+        args.listen, args.forward, args.delay, args.jitter, args.loss, args.duplicate
+    );
+
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(1, |d| d.as_nanos() as u64 | 1);
+    let mut rng = Rng(seed);
+    let mut client: Option<SocketAddr> = None;
+    let mut queue: BinaryHeap<Reverse<(Instant, u64, SocketAddr, Vec<u8>)>> = BinaryHeap::new();
+    let mut order = 0u64;
+    let mut datagram = [0; 2048];
+
+    loop {
+        match socket.recv_from(&mut datagram) {
+            Ok((len, from)) => {
+                let to = if from == args.forward {
+                    client
+                } else {
+                    client = Some(from);
+                    Some(args.forward)
+                };
+                if let Some(to) = to {
+                    if rng.below(100) < u64::from(args.loss) {
+                        continue;
+                    }
+                    let copies = if rng.below(100) < u64::from(args.duplicate) {
+                        2
+                    } else {
+                        1
+                    };
+                    for _ in 0..copies {
+                        let latency = args.delay + rng.below(args.jitter + 1);
+                        let due = Instant::now() + Duration::from_millis(latency);
+                        queue.push(Reverse((due, order, to, datagram[..len].to_vec())));
+                        order += 1;
+                    }
+                }
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => (),
+            Err(e) => eprintln!("{e}"),
+        }
+
+        while queue
+            .peek()
+            .is_some_and(|Reverse((due, ..))| *due <= Instant::now())
+        {
+            let Reverse((_, _, to, data)) = queue.pop().unwrap();
+            let _ = socket.send_to(&data, to);
+        }
+    }
+}
@@ -0,0 +1,33 @@
+/// Debug panel drawn over the emulation, shown and hidden with its own key.
+pub(crate) trait Overlay {
+    fn visible(&self) -> bool;
+    /// Handles the toggle key and, while visible, the overlay keys. Returns
+    /// true when visible: the keyboard then belongs to the overlay.
+    fn update(&mut self) -> bool;
+    fn draw(&self);
+}
+
+/// The overlays of the emulation window, one visible at a time.
+pub(crate) struct Overlays {
+    overlays: Vec<Box<dyn Overlay>>,
+}
+
+impl Overlays {
+    pub(crate) fn new(overlays: Vec<Box<dyn Overlay>>) -> Self {
+        Self { overlays }
+    }
+
+    /// Returns true when an overlay is visible.
+    pub(crate) fn update(&mut self) -> bool {
+        if let Some(overlay) = self.overlays.iter_mut().find(|o| o.visible()) {
+            return overlay.update();
+        }
+        self.overlays.iter_mut().any(|o| o.update())
+    }
+
+    pub(crate) fn draw(&self) {
+        for overlay in &self.overlays {
+            overlay.draw();
+        }
+    }
+}
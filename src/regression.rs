@@ -0,0 +1,353 @@
+//! `holani-min test`: runs cartridges headless with recorded inputs and
+//! compares the last frame and the sound against known hashes, to catch
+//! emulation changes when the `holani` dependency is updated.
+//!
+//! The manifest is a JSON array, paths are relative to it:
+//!
+//! ```json
+//! [
+//!   {
+//!     "name": "title",
+//!     "cartridge": "game.lnx",
+//!     "rom": "lynxboot.img",
+//!     "movie": "title.movie",
+//!     "frames": 600,
+//!     "framebuffer": "5c1f0b2e9d3a4c71",
+//!     "audio": "0e4d6a92c3b1f855",
+//!     "reference": "title.png"
+//!   }
+//! ]
+//! ```
+//!
+//! Names are made of letters, digits, `-`, `_` and `.`, not starting with a
+//! `.`, they name the screens written to `--output`. `rom`, `movie` and
+//! `reference` are optional. Tests without the hashes fail unless
+//! `--allow-unchecked` is given, run `--update` to record them.
+//! A movie holds
+//! `<frame> <buttons>` lines, the buttons being held from that frame on:
+//!
+//! ```text
+//! 0   -
+//! 120 pause
+//! 121 -
+//! 300 up,inside
+//! ```
+
+use crate::{
+    runner::{fnv1a, load_lynx, run_frame, runner_config::Input, SAMPLE_TICKS},
+    screenshot,
+};
+use clap::Args;
+use holani::{
+    lynx::Lynx,
+    mikey::video::{LYNX_SCREEN_HEIGHT, LYNX_SCREEN_WIDTH},
+    suzy::registers::{Joystick, Switches},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+#[derive(Args)]
+pub(crate) struct TestArgs {
+    /// Test manifest, JSON
+    manifest: PathBuf,
+
+    /// Where the screens of the failing tests are written
+    #[arg(long, value_name = "DIR", default_value = "test-output")]
+    output: PathBuf,
+
+    /// Record the current hashes and reference screens in the manifest
+    #[arg(long, default_value_t = false)]
+    update: bool,
+
+    /// Pass the tests without recorded hashes instead of failing them
+    #[arg(long, default_value_t = false)]
+    allow_unchecked: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TestCase {
+    name: String,
+    cartridge: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rom: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    movie: Option<PathBuf>,
+    frames: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    framebuffer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    audio: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reference: Option<PathBuf>,
+}
+
+struct Outcome {
+    screen: Vec<u8>,
+    framebuffer: String,
+    audio: String,
+}
+
+fn hash(data: &[u8]) -> String {
+    format!("{:016x}", fnv1a(data))
+}
+
+#[derive(PartialEq)]
+enum Verdict {
+    Pass,
+    Fail,
+    /// Some hash isn't recorded, none differs.
+    Unchecked,
+}
+
+/// Joystick and switches from each frame on.
+fn load_movie(path: &Path) -> Result<BTreeMap<u32, (u8, u8)>, String> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|err| format!("Couldn't read movie '{}': {err}.", path.display()))?;
+    let mut movie = BTreeMap::new();
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |msg: String| format!("{}:{}: {msg}", path.display(), number + 1);
+        let (frame, buttons) = line.split_once(char::is_whitespace).unwrap_or((line, "-"));
+        let frame = frame
+            .parse()
+            .map_err(|_| error(format!("Invalid frame '{frame}'.")))?;
+        let mut joystick = Joystick::empty();
+        let mut switches = Switches::empty();
+        for name in buttons.trim().split(',').filter(|name| *name != "-") {
+            name.trim()
+                .parse::<Input>()
+                .map_err(error)?
+                .set(&mut joystick, &mut switches, true);
+        }
+        movie.insert(frame, (joystick.bits(), switches.bits()));
+    }
+    Ok(movie)
+}
+
+fn run_test(test: &TestCase, base: &Path) -> Result<Outcome, String> {
+    let movie = match &test.movie {
+        Some(movie) => load_movie(&base.join(movie))?,
+        None => BTreeMap::new(),
+    };
+    let rom = test.rom.as_ref().map(|rom| base.join(rom));
+    let mut lynx = load_lynx(rom.as_deref(), &base.join(&test.cartridge), None)?;
+
+    let mut audio = vec![];
+    let mut sound_tick = 0;
+    let mut sample = |lynx: &Lynx| {
+        sound_tick += 1;
+        if sound_tick == SAMPLE_TICKS {
+            sound_tick = 0;
+            let (l, r) = lynx.audio_sample();
+            audio.extend(l.to_le_bytes());
+            audio.extend(r.to_le_bytes());
+        }
+    };
+    for frame in 0..test.frames {
+        if let Some((joystick, switches)) = movie.get(&frame) {
+            lynx.set_joystick_u8(*joystick);
+            lynx.set_switches_u8(*switches);
+        }
+        run_frame(&mut lynx, &mut sample);
+    }
+
+    let screen = lynx.screen_rgba().clone();
+    Ok(Outcome {
+        framebuffer: hash(&screen),
+        audio: hash(&audio),
+        screen,
+    })
+}
+
+fn load_png(path: &Path) -> Result<Vec<u8>, String> {
+    let error = |err: &dyn std::fmt::Display| format!("Couldn't read '{}': {err}.", path.display());
+    let file = File::open(path).map_err(|err| error(&err))?;
+    let mut reader = png::Decoder::new(BufReader::new(file))
+        .read_info()
+        .map_err(|err| error(&err))?;
+    let mut rgba = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut rgba).map_err(|err| error(&err))?;
+    if info.color_type != png::ColorType::Rgba
+        || info.width != LYNX_SCREEN_WIDTH
+        || info.height != LYNX_SCREEN_HEIGHT
+    {
+        return Err(error(&"not a RGBA Lynx screen"));
+    }
+    rgba.truncate(info.buffer_size());
+    Ok(rgba)
+}
+
+/// The actual screen, dimmed, with the pixels that differ from the
+/// reference in red.
+fn diff(actual: &[u8], reference: &[u8]) -> Vec<u8> {
+    actual
+        .chunks(4)
+        .zip(reference.chunks(4))
+        .flat_map(|(a, r)| {
+            if a == r {
+                [a[0] / 4, a[1] / 4, a[2] / 4, 0xFF]
+            } else {
+                [0xFF, 0, 0, 0xFF]
+            }
+        })
+        .collect()
+}
+
+fn write_screens(test: &TestCase, base: &Path, output: &Path, screen: &[u8]) -> Result<(), String> {
+    std::fs::create_dir_all(output)
+        .map_err(|err| format!("Couldn't create '{}': {err}.", output.display()))?;
+    let actual = output.join(format!("{}.actual.png", test.name));
+    screenshot::save(&actual, screen)?;
+    println!("       screen: {}", actual.display());
+    if let Some(reference) = &test.reference {
+        let reference = load_png(&base.join(reference))?;
+        let path = output.join(format!("{}.diff.png", test.name));
+        screenshot::save(&path, &diff(screen, &reference))?;
+        println!("       diff:   {}", path.display());
+    }
+    Ok(())
+}
+
+/// Compares the hashes. Returns the notes to print under the test.
+fn check(test: &TestCase, outcome: &Outcome) -> (Verdict, Vec<String>) {
+    let mut verdict = Verdict::Pass;
+    let mut notes = vec![];
+    for (what, expected, actual) in [
+        ("framebuffer", &test.framebuffer, &outcome.framebuffer),
+        ("audio", &test.audio, &outcome.audio),
+    ] {
+        match expected {
+            Some(expected) if expected != actual => {
+                notes.push(format!("{what}: expected {expected}, got {actual}"));
+                verdict = Verdict::Fail;
+            }
+            Some(_) => {}
+            None => {
+                notes.push(format!("{what}: {actual} (not recorded)"));
+                if verdict == Verdict::Pass {
+                    verdict = Verdict::Unchecked;
+                }
+            }
+        }
+    }
+    (verdict, notes)
+}
+
+fn update(test: &mut TestCase, base: &Path, outcome: Outcome) -> Result<(), String> {
+    if let Some(reference) = &test.reference {
+        screenshot::save(&base.join(reference), &outcome.screen)?;
+    }
+    test.framebuffer = Some(outcome.framebuffer);
+    test.audio = Some(outcome.audio);
+    Ok(())
+}
+
+/// Test names end up in file names, they can't leave `--output`.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn exit_with(err: &str) -> ! {
+    println!("Error: {err}");
+    std::process::exit(1);
+}
+
+pub(crate) fn run(args: &TestArgs) {
+    let data = std::fs::read_to_string(&args.manifest).unwrap_or_else(|err| {
+        exit_with(&format!(
+            "Couldn't read '{}': {err}.",
+            args.manifest.display()
+        ))
+    });
+    let mut tests: Vec<TestCase> = serde_json::from_str(&data).unwrap_or_else(|err| {
+        exit_with(&format!(
+            "Invalid manifest '{}': {err}.",
+            args.manifest.display()
+        ))
+    });
+    if let Some(test) = tests.iter().find(|test| !valid_name(&test.name)) {
+        exit_with(&format!(
+            "Invalid test name '{}', use letters, digits, '-', '_' and '.'.",
+            test.name
+        ));
+    }
+    let base = args
+        .manifest
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+
+    let mut failed = 0;
+    let mut unchecked = 0;
+    for test in &mut tests {
+        let outcome = match run_test(test, &base) {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                println!("ERROR  {}: {err}", test.name);
+                failed += 1;
+                continue;
+            }
+        };
+        if args.update {
+            println!("UPDATE {}", test.name);
+            if let Err(err) = update(test, &base, outcome) {
+                println!("       {err}");
+                failed += 1;
+            }
+            continue;
+        }
+        let (verdict, notes) = check(test, &outcome);
+        let pass = match verdict {
+            Verdict::Pass => true,
+            Verdict::Fail => false,
+            Verdict::Unchecked => {
+                unchecked += 1;
+                args.allow_unchecked
+            }
+        };
+        let label = match verdict {
+            Verdict::Pass => "PASS  ",
+            Verdict::Fail => "FAIL  ",
+            Verdict::Unchecked => "UNCHK ",
+        };
+        println!("{label} {}", test.name);
+        for note in notes {
+            println!("       {note}");
+        }
+        if !pass {
+            failed += 1;
+            if let Err(err) = write_screens(test, &base, &args.output, &outcome.screen) {
+                println!("       {err}");
+            }
+        }
+    }
+
+    if args.update {
+        let data = serde_json::to_string_pretty(&tests).unwrap_or_default();
+        std::fs::write(&args.manifest, data + "\n").unwrap_or_else(|err| {
+            exit_with(&format!(
+                "Couldn't write '{}': {err}.",
+                args.manifest.display()
+            ))
+        });
+    }
+    println!(
+        "{} test(s), {failed} failed, {unchecked} unchecked.",
+        tests.len()
+    );
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
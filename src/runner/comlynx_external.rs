@@ -0,0 +1,184 @@
+use super::{
+    comlynx_link::{ComlynxLink, LinkStatus},
+    comlynx_runner_thread::TICK_GROUP,
+    comlynx_transport::{ComlynxListener, Transport},
+    RunnerConfig,
+};
+use holani::lynx::Lynx;
+use std::collections::VecDeque;
+
+/// Keeps two instances within one window of emulated cycles of each other.
+///
+/// Every instance announces each window boundary it reaches and doesn't start
+/// a window before its peer reached it as well. Received bytes are held back
+/// one full window after the cycle they were sent at; by then the peer's
+/// announcement proves they have arrived, so they're delivered at the exact
+/// same emulated cycle whatever the host load.
+struct Lockstep {
+    window: u64,
+    last_sync: Option<u64>,
+    pending: VecDeque<(u64, u8)>,
+}
+
+impl Lockstep {
+    fn new(window: u32) -> Self {
+        Self {
+            window: u64::from(window).next_multiple_of(u64::from(TICK_GROUP)),
+            last_sync: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.last_sync = None;
+        self.pending.clear();
+    }
+
+    fn sync_due(&mut self, cycle: u64) -> bool {
+        if cycle % self.window != 0 || self.last_sync == Some(cycle) {
+            return false;
+        }
+        self.last_sync = Some(cycle);
+        true
+    }
+
+    fn may_run(&self, cycle: u64, peer_cycle: u64) -> bool {
+        peer_cycle >= cycle - cycle % self.window
+    }
+
+    fn next_due(&mut self, cycle: u64) -> Option<u8> {
+        match self.pending.front() {
+            Some((sent, _)) if sent + self.window <= cycle => {
+                self.pending.pop_front().map(|(_, data)| data)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Runner side of an external Comlynx connection: accepts peers, feeds the
+/// `Lynx` with what they send and optionally keeps both in lockstep.
+///
+/// The listener outlives it, a restarted runner gets the next peer.
+pub(crate) struct ComlynxExternal {
+    conn_rx: kanal::Receiver<Box<dyn Transport>>,
+    rearm_tx: kanal::Sender<()>,
+    link: Option<ComlynxLink>,
+    received: Vec<(u64, u8)>,
+    cable_present: bool,
+    epoch: u64,
+    raw: bool,
+    cart_hash: u64,
+    lockstep_window: u32,
+    lockstep: Option<Lockstep>,
+}
+
+impl ComlynxExternal {
+    pub(crate) fn new(config: &RunnerConfig, cart_hash: u64, listener: ComlynxListener) -> Self {
+        let (conn_rx, rearm_tx) = listener;
+        Self {
+            conn_rx,
+            rearm_tx,
+            link: None,
+            received: vec![],
+            cable_present: false,
+            epoch: 0,
+            raw: config.comlynx_raw(),
+            cart_hash,
+            lockstep_window: config.comlynx_lockstep().unwrap_or(0),
+            lockstep: config.comlynx_lockstep().map(Lockstep::new),
+        }
+    }
+
+    /// Services the connection; returns false while the lockstep holds the
+    /// emulation back waiting for the peer.
+    pub(crate) fn before_ticks(&mut self, lynx: &mut Lynx, cycles: u64) -> bool {
+        if let Ok(Some(s)) = self.conn_rx.try_recv() {
+            self.link = Some(ComlynxLink::new(
+                s,
+                self.raw,
+                self.cart_hash,
+                self.lockstep_window,
+            ));
+            self.disconnect(lynx);
+            println!("Comlynx client connected.");
+        }
+
+        match self.link.as_mut().map(|l| l.poll(&mut self.received)) {
+            Some(LinkStatus::Ready) => {
+                if !self.cable_present {
+                    self.cable_present = true;
+                    self.epoch = cycles;
+                    lynx.set_comlynx_cable_present(true);
+                    println!("Comlynx link established.");
+                }
+            }
+            Some(LinkStatus::Closed(reason)) => {
+                let _ = self.link.take();
+                self.disconnect(lynx);
+                println!("Comlynx client disconnected: {reason}.");
+                let _ = self.rearm_tx.send(());
+                return true;
+            }
+            Some(LinkStatus::Handshaking) | None => return true,
+        }
+
+        let cycle = cycles - self.epoch;
+        let link = self.link.as_mut().unwrap();
+        match self.lockstep.as_mut() {
+            None => {
+                for (_, data) in self.received.drain(..) {
+                    lynx.comlynx_ext_rx(data);
+                }
+                true
+            }
+            Some(lockstep) => {
+                lockstep.pending.extend(self.received.drain(..));
+                if lockstep.sync_due(cycle) {
+                    link.send_sync(cycle);
+                }
+                if !lockstep.may_run(cycle, link.peer_cycle()) {
+                    return false;
+                }
+                while let Some(data) = lockstep.next_due(cycle) {
+                    lynx.comlynx_ext_rx(data);
+                }
+                true
+            }
+        }
+    }
+
+    /// Plugs the cable into a Lynx that replaced the linked one, after a
+    /// reset or a state load.
+    pub(crate) fn replaced(&self, lynx: &mut Lynx) {
+        if self.cable_present {
+            lynx.set_comlynx_cable_present(true);
+        }
+    }
+
+    pub(crate) fn after_ticks(&mut self, lynx: &mut Lynx, cycles: u64) {
+        if let Some(tx) = lynx.comlynx_ext_tx() {
+            if let Some(l) = self.link.as_mut() {
+                l.send(tx, cycles - self.epoch);
+            }
+        }
+    }
+
+    fn disconnect(&mut self, lynx: &mut Lynx) {
+        self.received.clear();
+        self.cable_present = false;
+        if let Some(lockstep) = self.lockstep.as_mut() {
+            lockstep.reset();
+        }
+        lynx.set_comlynx_cable_present(false);
+    }
+}
+
+impl Drop for ComlynxExternal {
+    /// Connectionless transports are handed out again to the next runner.
+    fn drop(&mut self) {
+        if self.link.take().is_some() {
+            let _ = self.rearm_tx.send(());
+        }
+    }
+}
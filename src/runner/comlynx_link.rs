@@ -0,0 +1,208 @@
+use super::{
+    comlynx_protocol::{Frame, FrameDecoder},
+    comlynx_transport::Transport,
+};
+use log::trace;
+use std::{
+    io::ErrorKind,
+    time::{Duration, Instant},
+};
+
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) enum LinkStatus {
+    Handshaking,
+    Ready,
+    Closed(String),
+}
+
+/// One end of an external Comlynx connection.
+///
+/// Unless `raw` is set, the stream carries framed messages: both sides open
+/// with a hello holding the protocol version and the cartridge hash, then
+/// exchange data frames stamped with the emulated cycle they were sent at.
+/// Keepalives are sent when the line is idle and a goodbye on shutdown.
+/// The hello is repeated until answered, for transports without connections.
+/// In lockstep mode sync frames report the emulated cycle reached by the peer.
+pub(crate) struct ComlynxLink {
+    stream: Box<dyn Transport>,
+    raw: bool,
+    cart_hash: u64,
+    lockstep_window: u32,
+    peer_cycle: u64,
+    ready: bool,
+    closed: bool,
+    decoder: FrameDecoder,
+    outgoing: Vec<u8>,
+    last_sent: Instant,
+    last_received: Instant,
+}
+
+impl ComlynxLink {
+    pub(crate) fn new(
+        stream: Box<dyn Transport>,
+        raw: bool,
+        cart_hash: u64,
+        lockstep_window: u32,
+    ) -> Self {
+        let mut link = Self {
+            stream,
+            raw,
+            cart_hash,
+            lockstep_window,
+            peer_cycle: 0,
+            ready: raw,
+            closed: false,
+            decoder: FrameDecoder::default(),
+            outgoing: vec![],
+            last_sent: Instant::now(),
+            last_received: Instant::now(),
+        };
+        if !raw {
+            link.queue(&Frame::hello(cart_hash, lockstep_window));
+        }
+        link
+    }
+
+    /// Reads whatever the peer sent, appending `(cycle, byte)` pairs to `received`.
+    /// In raw mode the cycle is always 0.
+    pub(crate) fn poll(&mut self, received: &mut Vec<(u64, u8)>) -> LinkStatus {
+        let mut buffer = [0; 128];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return LinkStatus::Closed("connection closed".to_string()),
+                Ok(len) => {
+                    self.last_received = Instant::now();
+                    if self.raw {
+                        received.extend(buffer.iter().take(len).map(|b| (0, *b)));
+                    } else {
+                        self.decoder.push(&buffer[..len]);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return LinkStatus::Closed(e.to_string()),
+            }
+        }
+
+        if !self.raw {
+            if let Err(reason) = self.process_frames(received) {
+                self.close();
+                return LinkStatus::Closed(reason);
+            }
+            if self.ready && self.last_received.elapsed() > PEER_TIMEOUT {
+                return LinkStatus::Closed("peer timed out".to_string());
+            }
+            if self.last_sent.elapsed() > KEEPALIVE_INTERVAL {
+                if self.ready {
+                    self.queue(&Frame::Keepalive);
+                } else {
+                    self.queue(&Frame::hello(self.cart_hash, self.lockstep_window));
+                }
+            }
+        }
+
+        if let Err(e) = self.flush() {
+            return LinkStatus::Closed(e.to_string());
+        }
+
+        if self.ready {
+            LinkStatus::Ready
+        } else {
+            LinkStatus::Handshaking
+        }
+    }
+
+    pub(crate) fn send(&mut self, byte: u8, cycle: u64) {
+        if self.raw {
+            self.outgoing.push(byte);
+        } else if self.ready {
+            self.queue(&Frame::Data { cycle, byte });
+        }
+    }
+
+    pub(crate) fn send_sync(&mut self, cycle: u64) {
+        if self.ready {
+            self.queue(&Frame::Sync { cycle });
+        }
+    }
+
+    pub(crate) fn peer_cycle(&self) -> u64 {
+        self.peer_cycle
+    }
+
+    fn process_frames(&mut self, received: &mut Vec<(u64, u8)>) -> Result<(), String> {
+        loop {
+            match self.decoder.next_frame() {
+                Err(e) => return Err(format!("protocol error, {e}")),
+                Ok(None) => return Ok(()),
+                Ok(Some(Frame::Hello {
+                    cart_hash,
+                    lockstep_window,
+                    ..
+                })) => {
+                    if cart_hash != self.cart_hash {
+                        return Err(format!(
+                            "peer runs a different cartridge ({cart_hash:016x}, expected {:016x})",
+                            self.cart_hash
+                        ));
+                    }
+                    if lockstep_window != self.lockstep_window {
+                        return Err(format!(
+                            "peer lockstep window is {lockstep_window} cycles, expected {} (0 is disabled)",
+                            self.lockstep_window
+                        ));
+                    }
+                    if !self.ready {
+                        trace!("Comlynx handshake completed.");
+                        self.ready = true;
+                        self.peer_cycle = 0;
+                    }
+                }
+                Ok(Some(_)) if !self.ready => {
+                    return Err("peer sent data before the handshake".to_string())
+                }
+                Ok(Some(Frame::Data { cycle, byte })) => received.push((cycle, byte)),
+                Ok(Some(Frame::Sync { cycle })) => self.peer_cycle = cycle,
+                Ok(Some(Frame::Keepalive)) => (),
+                Ok(Some(Frame::Goodbye)) => return Err("peer said goodbye".to_string()),
+            }
+        }
+    }
+
+    fn queue(&mut self, frame: &Frame) {
+        frame.encode(&mut self.outgoing);
+        self.last_sent = Instant::now();
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(len) => {
+                    self.outgoing.drain(..len);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if self.raw || self.closed {
+            return;
+        }
+        self.closed = true;
+        self.queue(&Frame::Goodbye);
+        let _ = self.flush();
+    }
+}
+
+impl Drop for ComlynxLink {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+
+pub const PROTOCOL_VERSION: u16 = 2;
+const MAGIC: [u8; 4] = *b"HLNX";
+
+const FRAME_HELLO: u8 = 0x01;
+const FRAME_DATA: u8 = 0x02;
+const FRAME_KEEPALIVE: u8 = 0x03;
+const FRAME_GOODBYE: u8 = 0x04;
+const FRAME_SYNC: u8 = 0x05;
+
+// type + payload length
+const FRAME_HEADER_LEN: usize = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Frame {
+    Hello {
+        version: u16,
+        cart_hash: u64,
+        lockstep_window: u32,
+    },
+    Data {
+        cycle: u64,
+        byte: u8,
+    },
+    Keepalive,
+    Goodbye,
+    Sync {
+        cycle: u64,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ProtocolError {
+    BadMagic,
+    UnknownFrame(u8),
+    BadLength(u8, usize),
+    VersionMismatch(u16),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::BadMagic => write!(f, "peer is not a holani Comlynx endpoint"),
+            ProtocolError::UnknownFrame(t) => write!(f, "unknown frame type 0x{t:02x}"),
+            ProtocolError::BadLength(t, l) => {
+                write!(f, "invalid payload length {l} for frame type 0x{t:02x}")
+            }
+            ProtocolError::VersionMismatch(v) => {
+                write!(
+                    f,
+                    "peer speaks protocol version {v}, expected {PROTOCOL_VERSION}"
+                )
+            }
+        }
+    }
+}
+
+impl Frame {
+    pub(crate) fn hello(cart_hash: u64, lockstep_window: u32) -> Self {
+        Frame::Hello {
+            version: PROTOCOL_VERSION,
+            cart_hash,
+            lockstep_window,
+        }
+    }
+
+    pub(crate) fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Frame::Hello {
+                version,
+                cart_hash,
+                lockstep_window,
+            } => {
+                out.extend_from_slice(&[FRAME_HELLO, 18]);
+                out.extend_from_slice(&MAGIC);
+                out.extend_from_slice(&version.to_le_bytes());
+                out.extend_from_slice(&cart_hash.to_le_bytes());
+                out.extend_from_slice(&lockstep_window.to_le_bytes());
+            }
+            Frame::Data { cycle, byte } => {
+                out.extend_from_slice(&[FRAME_DATA, 9]);
+                out.extend_from_slice(&cycle.to_le_bytes());
+                out.push(*byte);
+            }
+            Frame::Keepalive => out.extend_from_slice(&[FRAME_KEEPALIVE, 0]),
+            Frame::Goodbye => out.extend_from_slice(&[FRAME_GOODBYE, 0]),
+            Frame::Sync { cycle } => {
+                out.extend_from_slice(&[FRAME_SYNC, 8]);
+                out.extend_from_slice(&cycle.to_le_bytes());
+            }
+        }
+    }
+
+    fn decode(frame_type: u8, payload: &[u8]) -> Result<Self, ProtocolError> {
+        // The magic and version lead the hello so that older peers are still told apart.
+        if frame_type == FRAME_HELLO && (payload.len() < 6 || payload[0..4] != MAGIC) {
+            return Err(ProtocolError::BadMagic);
+        }
+        if frame_type == FRAME_HELLO {
+            let version = u16::from_le_bytes([payload[4], payload[5]]);
+            if version != PROTOCOL_VERSION {
+                return Err(ProtocolError::VersionMismatch(version));
+            }
+        }
+        let expected = match frame_type {
+            FRAME_HELLO => 18,
+            FRAME_DATA => 9,
+            FRAME_SYNC => 8,
+            FRAME_KEEPALIVE | FRAME_GOODBYE => 0,
+            t => return Err(ProtocolError::UnknownFrame(t)),
+        };
+        if payload.len() != expected {
+            return Err(ProtocolError::BadLength(frame_type, payload.len()));
+        }
+        Ok(match frame_type {
+            FRAME_HELLO => Frame::Hello {
+                version: u16::from_le_bytes([payload[4], payload[5]]),
+                cart_hash: u64::from_le_bytes(payload[6..14].try_into().unwrap()),
+                lockstep_window: u32::from_le_bytes(payload[14..18].try_into().unwrap()),
+            },
+            FRAME_DATA => Frame::Data {
+                cycle: u64::from_le_bytes(payload[0..8].try_into().unwrap()),
+                byte: payload[8],
+            },
+            FRAME_SYNC => Frame::Sync {
+                cycle: u64::from_le_bytes(payload[0..8].try_into().unwrap()),
+            },
+            FRAME_KEEPALIVE => Frame::Keepalive,
+            _ => Frame::Goodbye,
+        })
+    }
+}
+
+/// Accumulates raw stream bytes and splits them into frames.
+#[derive(Default)]
+pub(crate) struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub(crate) fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    pub(crate) fn next_frame(&mut self) -> Result<Option<Frame>, ProtocolError> {
+        if self.buffer.len() < FRAME_HEADER_LEN {
+            return Ok(None);
+        }
+        let frame_type = self.buffer[0];
+        let len = self.buffer[1] as usize;
+        if self.buffer.len() < FRAME_HEADER_LEN + len {
+            return Ok(None);
+        }
+        let frame = Frame::decode(
+            frame_type,
+            &self.buffer[FRAME_HEADER_LEN..FRAME_HEADER_LEN + len],
+        );
+        self.buffer.drain(..FRAME_HEADER_LEN + len);
+        frame.map(Some)
+    }
+}
+
+/// Length of the whole frames leading `data`, no more than `max` bytes.
+pub(crate) fn whole_frames_len(data: &[u8], max: usize) -> usize {
+    let mut len = 0;
+    while let Some(payload_len) = data.get(len + 1) {
+        let end = len + FRAME_HEADER_LEN + usize::from(*payload_len);
+        if end > data.len() || end > max {
+            break;
+        }
+        len = end;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames() -> Vec<Frame> {
+        vec![
+            Frame::hello(0x0123_4567_89ab_cdef, 16_000),
+            Frame::Data {
+                cycle: u64::MAX,
+                byte: 0xa5,
+            },
+            Frame::Keepalive,
+            Frame::Goodbye,
+            Frame::Sync { cycle: 42 },
+        ]
+    }
+
+    fn decode(data: &[u8]) -> Result<Option<Frame>, ProtocolError> {
+        let mut decoder = FrameDecoder::default();
+        decoder.push(data);
+        decoder.next_frame()
+    }
+
+    fn hello(version: u16) -> Vec<u8> {
+        let mut data = vec![];
+        Frame::Hello {
+            version,
+            cart_hash: 1,
+            lockstep_window: 0,
+        }
+        .encode(&mut data);
+        data
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let mut data = vec![];
+        for frame in frames() {
+            frame.encode(&mut data);
+        }
+        let mut decoder = FrameDecoder::default();
+        decoder.push(&data);
+        for frame in frames() {
+            assert_eq!(decoder.next_frame(), Ok(Some(frame)));
+        }
+        assert_eq!(decoder.next_frame(), Ok(None));
+    }
+
+    #[test]
+    fn partial_frames_wait_for_the_rest() {
+        let mut data = vec![];
+        for frame in frames() {
+            frame.encode(&mut data);
+        }
+        let mut decoder = FrameDecoder::default();
+        let mut decoded = vec![];
+        for byte in data {
+            decoder.push(&[byte]);
+            if let Some(frame) = decoder.next_frame().unwrap() {
+                decoded.push(frame);
+            }
+        }
+        assert_eq!(decoded, frames());
+    }
+
+    #[test]
+    fn hello_is_checked() {
+        let mut data = hello(PROTOCOL_VERSION);
+        data[3] = b'X';
+        assert_eq!(decode(&data), Err(ProtocolError::BadMagic));
+        assert_eq!(
+            decode(&[FRAME_HELLO, 2, b'H', b'L']),
+            Err(ProtocolError::BadMagic)
+        );
+        assert_eq!(
+            decode(&hello(PROTOCOL_VERSION + 1)),
+            Err(ProtocolError::VersionMismatch(PROTOCOL_VERSION + 1))
+        );
+
+        let mut data = hello(PROTOCOL_VERSION);
+        data[1] = 17;
+        data.pop();
+        assert_eq!(
+            decode(&data),
+            Err(ProtocolError::BadLength(FRAME_HELLO, 17))
+        );
+    }
+
+    #[test]
+    fn bad_frames_are_rejected() {
+        assert_eq!(decode(&[0x7f, 0]), Err(ProtocolError::UnknownFrame(0x7f)));
+        assert_eq!(
+            decode(&[FRAME_DATA, 1, 0]),
+            Err(ProtocolError::BadLength(FRAME_DATA, 1))
+        );
+        assert_eq!(
+            decode(&[FRAME_KEEPALIVE, 1, 0]),
+            Err(ProtocolError::BadLength(FRAME_KEEPALIVE, 1))
+        );
+    }
+
+    #[test]
+    fn bad_frames_are_consumed() {
+        let mut decoder = FrameDecoder::default();
+        decoder.push(&[0x7f, 1, 0, FRAME_GOODBYE, 0]);
+        assert_eq!(decoder.next_frame(), Err(ProtocolError::UnknownFrame(0x7f)));
+        assert_eq!(decoder.next_frame(), Ok(Some(Frame::Goodbye)));
+    }
+
+    #[test]
+    fn whole_frames_are_measured() {
+        let mut data = vec![];
+        Frame::Keepalive.encode(&mut data);
+        Frame::Sync { cycle: 0 }.encode(&mut data);
+        let len = data.len();
+        assert_eq!(whole_frames_len(&data, usize::MAX), len);
+        assert_eq!(whole_frames_len(&data, len - 1), FRAME_HEADER_LEN);
+        assert_eq!(whole_frames_len(&data, 1), 0);
+        assert_eq!(
+            whole_frames_len(&data[..len - 1], usize::MAX),
+            FRAME_HEADER_LEN
+        );
+        assert_eq!(whole_frames_len(&data[..1], usize::MAX), 0);
+        assert_eq!(whole_frames_len(&[], usize::MAX), 0);
+    }
+}
@@ -1,5 +1,15 @@
-use super::{RunnerConfig, RunnerThread, CRYSTAL_FREQUENCY, SAMPLE_TICKS};
-use crate::{runner::SAMPLE_RATE, sound_source::SoundSource};
+#[cfg(feature = "comlynx_external")]
+use super::{comlynx_external::ComlynxExternal, comlynx_transport::ComlynxListener, fnv1a};
+use super::{
+    RunnerCommand, RunnerConfig, RunnerHooks, RunnerThread, CRYSTAL_FREQUENCY, SAMPLE_TICKS,
+};
+use crate::{
+    debugger::{trace::Tracer, Debugger},
+    gamedb,
+    runner::SAMPLE_RATE,
+    script::Script,
+    sound_source::SoundSource,
+};
 use holani::{cartridge::lnx_header::LNXRotation, lynx::Lynx};
 use log::trace;
 use ringbuf::{
@@ -9,7 +19,7 @@ use ringbuf::{
 use rodio::{OutputStream, Sink};
 use std::time::{Duration, Instant};
 
-const TICK_GROUP: u32 = 8;
+pub(super) const TICK_GROUP: u32 = 8;
 const TICK_LENGTH: Duration =
     Duration::from_nanos((1_000_000_000f32 / CRYSTAL_FREQUENCY as f32 * TICK_GROUP as f32) as u64);
 
@@ -21,8 +31,19 @@ pub(crate) struct ComlynxRunnerThread {
     input_rx: kanal::Receiver<(u8, u8)>,
     update_display_tx: kanal::Sender<Vec<u8>>,
     rotation_tx: kanal::Sender<LNXRotation>,
+    command_rx: kanal::Receiver<RunnerCommand>,
     sink: Option<Sink>,
     stream: Option<OutputStream>,
+    debugger: Option<Debugger>,
+    tracer: Option<Tracer>,
+    script: Option<Script>,
+    hooks: RunnerHooks,
+    #[cfg(feature = "comlynx_external")]
+    cart_hash: u64,
+    #[cfg(feature = "comlynx_external")]
+    cycles: u64,
+    #[cfg(feature = "comlynx_external")]
+    comlynx_listener: Option<ComlynxListener>,
 }
 
 impl ComlynxRunnerThread {
@@ -31,7 +52,10 @@ impl ComlynxRunnerThread {
         input_rx: kanal::Receiver<(u8, u8)>,
         update_display_tx: kanal::Sender<Vec<u8>>,
         rotation_tx: kanal::Sender<LNXRotation>,
+        command_rx: kanal::Receiver<RunnerCommand>,
+        debugger: Option<Debugger>,
     ) -> Self {
+        let hooks = RunnerHooks::new(&config);
         Self {
             lynx: Lynx::new(),
             next_ticks_trigger: Instant::now(),
@@ -39,14 +63,32 @@ impl ComlynxRunnerThread {
             input_rx,
             update_display_tx,
             rotation_tx,
+            command_rx,
             sound_tick: 0,
             sink: None,
             stream: None,
+            debugger,
+            tracer: None,
+            script: None,
+            hooks,
+            #[cfg(feature = "comlynx_external")]
+            cart_hash: 0,
+            #[cfg(feature = "comlynx_external")]
+            cycles: 0,
+            #[cfg(feature = "comlynx_external")]
+            comlynx_listener: None,
         }
     }
 
+    /// The listener is kept by the `Runner` across restarts, a new one
+    /// couldn't bind the endpoint the previous one holds.
+    #[cfg(feature = "comlynx_external")]
+    pub(crate) fn set_comlynx_listener(&mut self, listener: ComlynxListener) {
+        self.comlynx_listener = Some(listener);
+    }
+
     fn sound(&mut self, prod: &mut HeapProd<i16>) {
-        if self.config.mute() {
+        if self.hooks.muted() {
             return;
         }
 
@@ -65,16 +107,54 @@ impl ComlynxRunnerThread {
             return;
         }
         trace!("Display updated.");
+        self.hooks.frame(&mut self.lynx);
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.frame();
+        }
+        if let Some(script) = self.script.as_mut() {
+            script.frame(&mut self.lynx, &mut self.hooks);
+        }
         let screen = self.lynx.screen_rgba().clone();
         let _ = self.update_display_tx.try_send(screen);
     }
 
+    fn debug(&mut self) -> bool {
+        match self.debugger.as_mut() {
+            Some(debugger) if debugger.check(&self.lynx) => {
+                let screen = self.lynx.screen_rgba().clone();
+                let _ = self.update_display_tx.try_send(screen);
+                debugger.wait(
+                    &mut self.lynx,
+                    &self.input_rx,
+                    &self.command_rx,
+                    &mut self.hooks,
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// Lets the debugger stop while the runner is paused.
+    fn debug_paused(&mut self) -> bool {
+        match self.debugger.as_mut() {
+            Some(debugger) if debugger.poll_paused(&mut self.lynx) => debugger.wait(
+                &mut self.lynx,
+                &self.input_rx,
+                &self.command_rx,
+                &mut self.hooks,
+            ),
+            _ => false,
+        }
+    }
+
     fn inputs(&mut self) -> bool {
         if self.input_rx.is_disconnected() {
             return true;
         } else if let Ok(Some((joy, sw))) = self.input_rx.try_recv() {
-            self.lynx.set_joystick_u8(joy);
-            self.lynx.set_switches_u8(sw);
+            self.hooks.input(&mut self.lynx, joy, sw);
+        }
+        while let Ok(Some(command)) = self.command_rx.try_recv() {
+            command.execute(&mut self.lynx, &mut self.hooks);
         }
         false
     }
@@ -96,18 +176,41 @@ impl RunnerThread for ComlynxRunnerThread {
         match self.config.cartridge() {
             None => panic!("A cartridge is required."),
             Some(cart) => {
-                let data = std::fs::read(cart);
+                let data = gamedb::read_cartridge(cart, self.config.bank_size());
                 if data.is_err() {
                     return Err("Couldn't not load Cartridge file.");
                 }
-                if self.lynx.load_cart_from_slice(&data.unwrap()).is_err() {
+                let data = data.unwrap();
+                if self.lynx.load_cart_from_slice(&data).is_err() {
                     return Err("Couldn't not load Cartridge file.");
                 }
+                #[cfg(feature = "comlynx_external")]
+                {
+                    self.cart_hash = fnv1a(&data);
+                }
                 trace!("ROM loaded.");
             }
         }
 
         trace!("Cart loaded.");
+
+        if let Some(trace) = self.config.trace() {
+            match Tracer::new(trace, self.config.symbols().clone()) {
+                Ok(tracer) => self.tracer = Some(tracer),
+                Err(_) => return Err("Couldn't create the trace file."),
+            }
+        }
+
+        if let Some(script) = self.config.script() {
+            match Script::new(script, &mut self.lynx, &mut self.hooks) {
+                Ok(script) => self.script = Some(script),
+                Err(err) => {
+                    println!("{err}");
+                    return Err("Couldn't start the script.");
+                }
+            }
+        }
+
         self.rotation_tx.send(self.lynx.rotation()).unwrap();
 
         Ok(())
@@ -118,33 +221,13 @@ impl RunnerThread for ComlynxRunnerThread {
         let (mut sound_buffer, sound_consumer) = sound_ringbuf.split();
 
         #[cfg(feature = "comlynx_external")]
-        let (tcp_conn_tx, tcp_conn_rx) = kanal::unbounded::<TcpStream>();
-        #[cfg(feature = "comlynx_external")]
-        let port = self.config.comlynx_port();
-        #[cfg(feature = "comlynx_external")]
-        let _tcplistener = std::thread::Builder::new()
-            .name("TCPListener".to_string())
-            .spawn_with_priority(ThreadPriority::Min, move |_| {
-                let bindto = format!("0.0.0.0:{}", port);
-                let tcpsock = std::net::TcpListener::bind(bindto).unwrap();
-                println!("Comlynx TCP server running at 0.0.0.0:{}", port);
-
-                for stream in tcpsock.incoming() {
-                    match stream {
-                        Ok(stream) => {
-                            stream.set_nonblocking(true).unwrap();
-                            tcp_conn_tx.send(stream).unwrap();
-                        }
-                        Err(e) => eprintln!("{}", e),
-                    }
-                }
-            })
-            .expect("Could not create the TCP listener thread.");
-
-        #[cfg(feature = "comlynx_external")]
-        let mut buffer = [0; 128];
-        #[cfg(feature = "comlynx_external")]
-        let mut stream: Option<TcpStream> = None;
+        let mut comlynx = ComlynxExternal::new(
+            &self.config,
+            self.cart_hash,
+            self.comlynx_listener
+                .take()
+                .expect("Comlynx listener not set."),
+        );
 
         if !self.config.mute() {
             let stream_handle = rodio::OutputStreamBuilder::open_default_stream()
@@ -165,37 +248,47 @@ impl RunnerThread for ComlynxRunnerThread {
                 return;
             }
 
-            for _ in 0..TICK_GROUP {
-                self.lynx.tick();
-                self.sound(&mut sound_buffer);
+            if self.hooks.paused() {
+                if self.debug_paused() {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+
+            if let Some(debugger) = self.debugger.as_mut() {
+                debugger.poll(&mut self.lynx);
             }
 
             #[cfg(feature = "comlynx_external")]
             {
-                if let Ok(Some(s)) = tcp_conn_rx.try_recv() {
-                    stream = Some(s);
-                    self.lynx.set_comlynx_cable_present(true);
-                    println!("Comlynx client connected.");
+                if self.hooks.take_replaced() {
+                    comlynx.replaced(&mut self.lynx);
                 }
-
-                match stream.as_ref().map(|mut s| s.read(&mut buffer)) {
-                    Some(Err(_)) => (),
-                    Some(Ok(0)) => {
-                        let _ = stream.take();
-                        self.lynx.set_comlynx_cable_present(false);
-                        println!("Comlynx client disconnected.");
-                    }
-                    Some(Ok(len)) => {
-                        for data in buffer.iter().take(len) {
-                            self.lynx.comlynx_ext_rx(*data);
-                        }
-                    }
-                    None => (),
+                if !comlynx.before_ticks(&mut self.lynx, self.cycles) {
+                    continue;
                 }
+            }
 
-                if let Some(tx) = self.lynx.comlynx_ext_tx() {
-                    let _ = stream.as_ref().map(|mut s| s.write_all(&[tx]));
+            for _ in 0..TICK_GROUP {
+                self.lynx.tick();
+                self.sound(&mut sound_buffer);
+                self.hooks.tick(&self.lynx);
+                if self.debugger.is_some() && self.debug() {
+                    return;
                 }
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer.tick(&self.lynx);
+                }
+                if let Some(script) = self.script.as_mut() {
+                    script.tick(&mut self.lynx, &mut self.hooks);
+                }
+            }
+
+            #[cfg(feature = "comlynx_external")]
+            {
+                self.cycles += u64::from(TICK_GROUP);
+                comlynx.after_ticks(&mut self.lynx, self.cycles);
             }
 
             self.display();
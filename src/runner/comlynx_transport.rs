@@ -0,0 +1,290 @@
+use super::comlynx_protocol::whole_frames_len;
+#[cfg(unix)]
+use crate::control::bind_unix;
+use log::trace;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::{
+    fmt,
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, UdpSocket},
+    str::FromStr,
+};
+use thread_priority::{ThreadBuilderExt, ThreadPriority};
+
+const UDP_HEADER_LEN: usize = 4;
+const UDP_MAX_PAYLOAD: usize = 1200;
+
+pub(crate) trait Transport: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Transport for T {}
+
+/// Where an external Comlynx instance listens or sends, parsed from `--comlynx`:
+/// a bare port or `tcp://host:port` for TCP, `unix:/path` for a Unix domain
+/// socket and `udp://peer:port[?bind=port]` for UDP.
+#[derive(Clone, Debug)]
+pub(crate) enum ComlynxEndpoint {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+    Udp {
+        bind: String,
+        peer: String,
+    },
+}
+
+impl FromStr for ComlynxEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(port) = s.parse::<u16>() {
+            return Ok(ComlynxEndpoint::Tcp(format!("0.0.0.0:{port}")));
+        }
+        if let Some(addr) = s.strip_prefix("tcp://") {
+            return Ok(ComlynxEndpoint::Tcp(addr.to_string()));
+        }
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(ComlynxEndpoint::Unix(PathBuf::from(
+                path.strip_prefix("//").unwrap_or(path),
+            )));
+            #[cfg(not(unix))]
+            return Err(format!(
+                "Unix domain sockets are not supported on this platform ({path})."
+            ));
+        }
+        if let Some(rest) = s.strip_prefix("udp://") {
+            let (peer, bind) = match rest.split_once("?bind=") {
+                Some((peer, bind)) => (peer, bind.to_string()),
+                None => {
+                    let port = rest
+                        .rsplit_once(':')
+                        .map(|(_, p)| p)
+                        .ok_or(format!("Missing port in '{s}'."))?;
+                    (rest, port.to_string())
+                }
+            };
+            let bind = bind
+                .parse::<u16>()
+                .map_err(|_| format!("Invalid bind port in '{s}'."))?;
+            return Ok(ComlynxEndpoint::Udp {
+                bind: format!("0.0.0.0:{bind}"),
+                peer: peer.to_string(),
+            });
+        }
+        Err(format!(
+            "Unknown Comlynx endpoint '{s}', expected <port>, tcp://host:port, unix:/path or udp://host:port."
+        ))
+    }
+}
+
+impl fmt::Display for ComlynxEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComlynxEndpoint::Tcp(addr) => write!(f, "tcp://{addr}"),
+            #[cfg(unix)]
+            ComlynxEndpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+            ComlynxEndpoint::Udp { bind, peer } => write!(f, "udp://{peer} (bound to {bind})"),
+        }
+    }
+}
+
+/// Datagram transport; every datagram is prefixed with a sequence number so
+/// duplicated and reordered packets are dropped instead of corrupting the stream.
+///
+/// Unless the link is raw, datagrams only carry whole frames and the ones that
+/// don't are dropped, so a lost datagram loses its frames but never leaves the
+/// receiver decoding from the middle of one.
+struct UdpTransport {
+    socket: UdpSocket,
+    framed: bool,
+    send_seq: u32,
+    recv_seq: Option<u32>,
+    pending: Vec<u8>,
+}
+
+impl UdpTransport {
+    fn new(socket: UdpSocket, framed: bool) -> Self {
+        Self {
+            socket,
+            framed,
+            send_seq: 0,
+            recv_seq: None,
+            pending: vec![],
+        }
+    }
+
+    fn accept_sequence(&mut self, seq: u32) -> bool {
+        match self.recv_seq {
+            // A restarted peer counts from zero again.
+            Some(last) if seq != 0 && seq.wrapping_sub(last) > u32::MAX / 2 => false,
+            Some(last) if seq == last => false,
+            Some(last) => {
+                if seq != 0 && seq != last.wrapping_add(1) {
+                    trace!(
+                        "Comlynx UDP: {} datagram(s) lost.",
+                        seq.wrapping_sub(last) - 1
+                    );
+                }
+                self.recv_seq = Some(seq);
+                true
+            }
+            None => {
+                self.recv_seq = Some(seq);
+                true
+            }
+        }
+    }
+}
+
+impl Read for UdpTransport {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut datagram = [0; UDP_HEADER_LEN + UDP_MAX_PAYLOAD];
+        while self.pending.is_empty() {
+            let len = match self.socket.recv(&mut datagram) {
+                // ICMP port unreachable from a peer that isn't up yet.
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                    return Err(ErrorKind::WouldBlock.into())
+                }
+                r => r?,
+            };
+            if len <= UDP_HEADER_LEN {
+                continue;
+            }
+            let payload = &datagram[UDP_HEADER_LEN..len];
+            if self.framed && whole_frames_len(payload, UDP_MAX_PAYLOAD) != payload.len() {
+                trace!("Comlynx UDP: dropped a datagram not made of whole frames.");
+                continue;
+            }
+            let seq = u32::from_le_bytes(datagram[0..UDP_HEADER_LEN].try_into().unwrap());
+            if self.accept_sequence(seq) {
+                self.pending.extend_from_slice(payload);
+            }
+        }
+        let len = buf.len().min(self.pending.len());
+        buf[..len].copy_from_slice(&self.pending[..len]);
+        self.pending.drain(..len);
+        Ok(len)
+    }
+}
+
+impl Write for UdpTransport {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = if self.framed {
+            whole_frames_len(buf, UDP_MAX_PAYLOAD)
+        } else {
+            buf.len().min(UDP_MAX_PAYLOAD)
+        };
+        if len == 0 && !buf.is_empty() {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Comlynx UDP: partial frame written",
+            ));
+        }
+        let mut datagram = Vec::with_capacity(UDP_HEADER_LEN + len);
+        datagram.extend_from_slice(&self.send_seq.to_le_bytes());
+        datagram.extend_from_slice(&buf[..len]);
+        match self.socket.send(&datagram) {
+            Ok(_) => {
+                self.send_seq = self.send_seq.wrapping_add(1);
+                Ok(len)
+            }
+            // Nobody listening on the other end yet, the handshake will be retried.
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => Ok(len),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Transports accepted by the listener, and the re-arm sender.
+pub(crate) type ComlynxListener = (kanal::Receiver<Box<dyn Transport>>, kanal::Sender<()>);
+
+/// Spawns the thread accepting Comlynx peers on `endpoint`.
+///
+/// Connected transports are sent non-blocking through the returned receiver.
+/// Connectionless transports are handed out once and again every time
+/// something is sent on the returned re-arm sender.
+///
+/// The thread ends once the returned receiver is dropped and the next peer
+/// connects, or at once for connectionless transports.
+pub(crate) fn spawn_listener(endpoint: ComlynxEndpoint, raw: bool) -> ComlynxListener {
+    let (conn_tx, conn_rx) = kanal::unbounded::<Box<dyn Transport>>();
+    let (rearm_tx, rearm_rx) = kanal::unbounded::<()>();
+
+    std::thread::Builder::new()
+        .name("ComlynxListener".to_string())
+        .spawn_with_priority(ThreadPriority::Min, move |_| match endpoint {
+            ComlynxEndpoint::Tcp(addr) => {
+                let listener = TcpListener::bind(&addr).unwrap_or_else(|err| {
+                    println!("Error: Couldn't listen on {addr}: {err}.");
+                    std::process::exit(1);
+                });
+                println!("Comlynx TCP server running at {addr}");
+                for stream in listener.incoming() {
+                    let stream = match stream
+                        .and_then(|s| s.set_nonblocking(true).map(|()| s))
+                        .and_then(|s| s.set_nodelay(true).map(|()| s))
+                    {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            println!("Comlynx client rejected: {e}.");
+                            continue;
+                        }
+                    };
+                    if conn_tx.send(Box::new(stream)).is_err() {
+                        return;
+                    }
+                }
+            }
+            #[cfg(unix)]
+            ComlynxEndpoint::Unix(path) => {
+                let listener = bind_unix(&path).unwrap_or_else(|err| {
+                    println!("Error: Couldn't listen on {}: {err}.", path.display());
+                    std::process::exit(1);
+                });
+                println!("Comlynx Unix socket server running at {}", path.display());
+                for stream in listener.incoming() {
+                    let stream = match stream.and_then(|s| s.set_nonblocking(true).map(|()| s)) {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            println!("Comlynx client rejected: {e}.");
+                            continue;
+                        }
+                    };
+                    if conn_tx.send(Box::new(stream)).is_err() {
+                        return;
+                    }
+                }
+            }
+            ComlynxEndpoint::Udp { bind, peer } => {
+                let socket = UdpSocket::bind(&bind)
+                    .and_then(|socket| socket.connect(&peer).map(|()| socket))
+                    .and_then(|socket| socket.set_nonblocking(true).map(|()| socket))
+                    .unwrap_or_else(|err| {
+                        println!("Error: Couldn't open UDP socket {bind} to {peer}: {err}.");
+                        std::process::exit(1);
+                    });
+                println!("Comlynx UDP socket bound to {bind}, peer {peer}");
+                loop {
+                    let socket = match socket.try_clone() {
+                        Ok(socket) => socket,
+                        Err(err) => {
+                            println!("Error: Couldn't reuse the Comlynx UDP socket: {err}.");
+                            return;
+                        }
+                    };
+                    let transport = UdpTransport::new(socket, !raw);
+                    if conn_tx.send(Box::new(transport)).is_err() || rearm_rx.recv().is_err() {
+                        return;
+                    }
+                }
+            }
+        })
+        .expect("Could not create the Comlynx listener thread.");
+
+    (conn_rx, rearm_tx)
+}
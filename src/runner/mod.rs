@@ -1,20 +1,329 @@
-use std::thread::JoinHandle;
+#[cfg(feature = "comlynx_external")]
+use crate::netplay::netplay_runner_thread::NetplayRunnerThread;
+use crate::{
+    cheats::Cheat,
+    control,
+    debugger::{
+        cpu, gdb, repl,
+        suzy::{SpriteCapture, SpriteFrame},
+        DebugCommand, DebugEvent, Debugger,
+    },
+    gamedb,
+    lyx::BankSizes,
+};
 use comlynx_runner_thread::ComlynxRunnerThread;
-use holani::cartridge::lnx_header::LNXRotation;
+#[cfg(feature = "comlynx_external")]
+use comlynx_transport::{spawn_listener, ComlynxListener};
+use holani::{cartridge::lnx_header::LNXRotation, lynx::Lynx};
 use log::trace;
 #[cfg(not(feature = "comlynx_external"))]
 use perframe_runner_thread::PerFrameRunnerThread;
 use runner_config::RunnerConfig;
+use std::{
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+};
 use thread_priority::*;
 
-pub(crate) mod runner_config;
+#[cfg(feature = "comlynx_external")]
+pub(crate) mod comlynx_external;
+#[cfg(feature = "comlynx_external")]
+pub(crate) mod comlynx_link;
+#[cfg(feature = "comlynx_external")]
+pub(crate) mod comlynx_protocol;
 pub(crate) mod comlynx_runner_thread;
+#[cfg(feature = "comlynx_external")]
+pub(crate) mod comlynx_transport;
 pub(crate) mod perframe_runner_thread;
+pub(crate) mod runner_config;
+pub(crate) mod state;
 
 pub const CRYSTAL_FREQUENCY: u32 = 16_000_000;
 pub const SAMPLE_RATE: u32 = 16_000;
 pub const SAMPLE_TICKS: u32 = CRYSTAL_FREQUENCY / SAMPLE_RATE;
 
+/// Creates a Lynx outside of the runner threads, for the headless commands.
+/// `banks` are the bank sizes of a raw dump, detected when `None`.
+pub(crate) fn load_lynx(
+    rom: Option<&Path>,
+    cartridge: &Path,
+    banks: Option<BankSizes>,
+) -> Result<Lynx, String> {
+    let mut lynx = Lynx::new();
+    if let Some(rom) = rom {
+        let data = std::fs::read(rom).map_err(|_| "Couldn't load ROM file.")?;
+        lynx.load_rom_from_slice(&data)
+            .map_err(|_| "Couldn't load ROM file.")?;
+    }
+    let data = gamedb::read_cartridge(cartridge, banks)
+        .map_err(|err| format!("Couldn't load Cartridge file: {err}."))?;
+    lynx.load_cart_from_slice(&data)
+        .map_err(|_| "Couldn't load Cartridge file.")?;
+    Ok(lynx)
+}
+
+/// FNV-1a, stable across builds and platforms. Hashes the cartridge image
+/// exchanged in the Comlynx and netplay handshakes, so that two instances
+/// running different games refuse to link, and the regression test outputs.
+pub(crate) fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Runs the Lynx until the end of the current frame.
+pub(crate) fn run_frame(lynx: &mut Lynx, mut tick: impl FnMut(&Lynx)) {
+    lynx.tick();
+    tick(lynx);
+    while !lynx.redraw_requested() {
+        lynx.tick();
+        tick(lynx);
+    }
+}
+
+/// Requests from the emulation window to the runner thread.
+pub(crate) enum RunnerCommand {
+    /// Replies with `len` bytes from `addr` as the CPU sees them, wrapping
+    /// around the 64K address space.
+    ReadMemory(u16, usize, kanal::Sender<Vec<u8>>),
+    Poke(u16, u8),
+    /// Replaces the cheats applied every frame.
+    SetCheats(Vec<Cheat>),
+    /// Replies with the sprites drawn during the next frame.
+    CaptureSprites(kanal::Sender<SpriteFrame>),
+    /// Stops or restarts the emulation, commands are still executed while paused.
+    Pause(bool),
+    /// Pauses a running emulation, resumes a paused one.
+    TogglePause,
+    /// Runs that many frames then pauses, replies once paused.
+    StepFrames(u32, kanal::Sender<()>),
+    SetButtons(u8, u8),
+    /// Joystick buttons to press and release every turbo rate frames.
+    SetTurbo(u8),
+    /// Stops feeding the audio output, the emulation goes on.
+    SetMute(bool),
+    /// Replies with the RGBA screen.
+    ReadScreen(kanal::Sender<Vec<u8>>),
+    SaveState(PathBuf, kanal::Sender<Result<(), String>>),
+    LoadState(PathBuf, kanal::Sender<Result<(), String>>),
+    /// Restarts the Lynx with the same ROM and cartridge, the runner state
+    /// (pause, buttons, cheats) is kept.
+    Reset(kanal::Sender<Result<(), String>>),
+    /// Like `Reset`, with the runner state back to how it started: running,
+    /// no buttons held.
+    PowerCycle(kanal::Sender<Result<(), String>>),
+}
+
+impl RunnerCommand {
+    pub(crate) fn execute(self, lynx: &mut Lynx, hooks: &mut RunnerHooks) {
+        match self {
+            RunnerCommand::ReadMemory(addr, len, reply_tx) => {
+                let _ = reply_tx.try_send(cpu::peek_range(lynx, addr, len));
+            }
+            RunnerCommand::Poke(addr, data) => cpu::poke(lynx, addr, data),
+            RunnerCommand::SetCheats(cheats) => hooks.cheats = cheats,
+            RunnerCommand::CaptureSprites(reply_tx) => {
+                hooks.sprite_capture = Some(SpriteCapture::new(reply_tx));
+            }
+            RunnerCommand::Pause(paused) => {
+                hooks.paused = paused;
+                hooks.stepping = None;
+            }
+            RunnerCommand::TogglePause => {
+                hooks.paused = !hooks.paused;
+                hooks.stepping = None;
+            }
+            RunnerCommand::StepFrames(0, reply_tx) => {
+                hooks.paused = true;
+                let _ = reply_tx.try_send(());
+            }
+            RunnerCommand::StepFrames(frames, reply_tx) => {
+                hooks.paused = false;
+                hooks.stepping = Some((frames, reply_tx));
+            }
+            RunnerCommand::SetButtons(joystick, switches) => hooks.input(lynx, joystick, switches),
+            RunnerCommand::SetTurbo(turbo) => {
+                hooks.turbo = turbo;
+                hooks.turbo_frame = 0;
+                hooks.apply_input(lynx);
+            }
+            RunnerCommand::SetMute(muted) => hooks.muted = muted,
+            RunnerCommand::ReadScreen(reply_tx) => {
+                let _ = reply_tx.try_send(lynx.screen_rgba().clone());
+            }
+            RunnerCommand::SaveState(path, reply_tx) => {
+                let _ = reply_tx.try_send(state::save_file(lynx, &path));
+            }
+            RunnerCommand::LoadState(path, reply_tx) => {
+                let _ = reply_tx.try_send(hooks.load_state(lynx, &path));
+            }
+            RunnerCommand::Reset(reply_tx) => {
+                let _ = reply_tx.try_send(hooks.reset(lynx, false));
+            }
+            RunnerCommand::PowerCycle(reply_tx) => {
+                let _ = reply_tx.try_send(hooks.reset(lynx, true));
+            }
+        }
+    }
+}
+
+/// What the runner commands set up in the runner thread, run on every tick
+/// and frame.
+pub(crate) struct RunnerHooks {
+    cheats: Vec<Cheat>,
+    sprite_capture: Option<SpriteCapture>,
+    paused: bool,
+    muted: bool,
+    /// Frames left to run before pausing again.
+    stepping: Option<(u32, kanal::Sender<()>)>,
+    joystick: u8,
+    switches: u8,
+    turbo: u8,
+    turbo_rate: u32,
+    turbo_frame: u32,
+    rom: Option<PathBuf>,
+    cartridge: Option<PathBuf>,
+    bank_size: Option<BankSizes>,
+    reset_after: Option<u32>,
+    frames_since_reset: u32,
+    /// The Lynx was replaced since the runner thread last asked, its Comlynx
+    /// cable is unplugged.
+    #[cfg(feature = "comlynx_external")]
+    replaced: bool,
+}
+
+impl RunnerHooks {
+    pub(crate) fn new(config: &RunnerConfig) -> Self {
+        Self {
+            cheats: vec![],
+            sprite_capture: None,
+            paused: false,
+            muted: config.mute(),
+            stepping: None,
+            joystick: 0,
+            switches: 0,
+            turbo: 0,
+            turbo_rate: config.turbo_rate().max(1),
+            turbo_frame: 0,
+            rom: config.rom().clone(),
+            cartridge: config.cartridge().clone(),
+            bank_size: config.bank_size(),
+            reset_after: config.reset_after(),
+            frames_since_reset: 0,
+            #[cfg(feature = "comlynx_external")]
+            replaced: false,
+        }
+    }
+
+    /// Replaces the Lynx with a new one, loaded from the same files.
+    pub(crate) fn reset(&mut self, lynx: &mut Lynx, power_cycle: bool) -> Result<(), String> {
+        let cartridge = self.cartridge.as_deref().ok_or("No cartridge loaded.")?;
+        *lynx = load_lynx(self.rom.as_deref(), cartridge, self.bank_size)?;
+        if power_cycle {
+            self.paused = false;
+            self.stepping = None;
+            self.joystick = 0;
+            self.switches = 0;
+            self.turbo = 0;
+        }
+        self.turbo_frame = 0;
+        self.frames_since_reset = 0;
+        #[cfg(feature = "comlynx_external")]
+        {
+            self.replaced = true;
+        }
+        self.apply_input(lynx);
+        trace!("Lynx reset.");
+        Ok(())
+    }
+
+    /// Replaces the Lynx with a saved one.
+    pub(crate) fn load_state(&mut self, lynx: &mut Lynx, path: &Path) -> Result<(), String> {
+        *lynx = state::load_file(path)?;
+        #[cfg(feature = "comlynx_external")]
+        {
+            self.replaced = true;
+        }
+        Ok(())
+    }
+
+    /// Whether the Lynx was replaced since the last call.
+    #[cfg(feature = "comlynx_external")]
+    pub(crate) fn take_replaced(&mut self) -> bool {
+        std::mem::take(&mut self.replaced)
+    }
+
+    /// Buttons from the player, the turbo ones are added on top.
+    pub(crate) fn input(&mut self, lynx: &mut Lynx, joystick: u8, switches: u8) {
+        self.joystick = joystick;
+        self.switches = switches;
+        self.apply_input(lynx);
+    }
+
+    /// Turbo buttons are pressed for `turbo_rate` frames, then released for
+    /// as many.
+    fn apply_input(&self, lynx: &mut Lynx) {
+        let pressed = (self.turbo_frame / self.turbo_rate) % 2 == 0;
+        let turbo = if pressed { self.turbo } else { 0 };
+        lynx.set_joystick_u8(self.joystick | turbo);
+        lynx.set_switches_u8(self.switches);
+    }
+
+    /// Buttons held by the player, without the turbo ones.
+    pub(crate) fn buttons(&self) -> (u8, u8) {
+        (self.joystick, self.switches)
+    }
+
+    pub(crate) fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub(crate) fn muted(&self) -> bool {
+        self.muted
+    }
+
+    pub(crate) fn tick(&mut self, lynx: &Lynx) {
+        if let Some(capture) = self.sprite_capture.as_mut() {
+            capture.tick(lynx);
+        }
+    }
+
+    pub(crate) fn frame(&mut self, lynx: &mut Lynx) {
+        for cheat in &self.cheats {
+            cheat.apply(lynx);
+        }
+        if self.turbo != 0 {
+            self.turbo_frame = self.turbo_frame.wrapping_add(1);
+            self.apply_input(lynx);
+        }
+        if self
+            .sprite_capture
+            .as_mut()
+            .is_some_and(SpriteCapture::frame)
+        {
+            self.sprite_capture = None;
+        }
+        if let Some((frames, _)) = self.stepping.as_mut() {
+            *frames -= 1;
+            if *frames == 0 {
+                if let Some((_, reply_tx)) = self.stepping.take() {
+                    let _ = reply_tx.try_send(());
+                }
+                self.paused = true;
+            }
+        }
+        if let Some(frames) = self.reset_after {
+            self.frames_since_reset += 1;
+            if self.frames_since_reset >= frames {
+                if let Err(err) = self.reset(lynx, false) {
+                    println!("Error: {err}");
+                    self.frames_since_reset = 0;
+                }
+            }
+        }
+    }
+}
+
 pub(crate) trait RunnerThread {
     fn initialize(&mut self) -> Result<(), &str>;
     fn run(&mut self);
@@ -23,61 +332,165 @@ pub(crate) trait RunnerThread {
 pub(crate) struct Runner {
     runner_thread: Option<JoinHandle<()>>,
     config: RunnerConfig,
-    input_tx: Option<kanal::Sender<u8>>,
+    input_tx: Option<kanal::Sender<(u8, u8)>>,
+    /// Outlive the runner threads, so that the control server and the
+    /// debugger front-ends keep running across restarts.
+    command_tx: kanal::Sender<RunnerCommand>,
+    command_rx: kanal::Receiver<RunnerCommand>,
+    debugger_channels: Option<(kanal::Receiver<DebugCommand>, kanal::Sender<DebugEvent>)>,
+    #[cfg(feature = "comlynx_external")]
+    comlynx_listener: Option<ComlynxListener>,
 }
 
 impl Drop for Runner {
     fn drop(&mut self) {
-        if let Some(tx) = self.input_tx.take() {
-            tx.close().unwrap();
-            if let Some(handle) = self.runner_thread.take() {
-                handle.join().unwrap();
-            }
-        }
+        self.stop();
     }
 }
 
 impl Runner {
     pub fn new(config: RunnerConfig) -> Self {
+        let (command_tx, command_rx) = kanal::unbounded::<RunnerCommand>();
+        if let Some(endpoint) = config.control() {
+            control::spawn(endpoint.clone(), command_tx.clone());
+        }
 
         Self {
             config,
             runner_thread: None,
             input_tx: None,
+            command_tx,
+            command_rx,
+            debugger_channels: None,
+            #[cfg(feature = "comlynx_external")]
+            comlynx_listener: None,
         }
     }
 
-    pub fn initialize_thread(&mut self) -> (kanal::Sender<(u8, u8)>, kanal::Receiver<Vec<u8>>, LNXRotation) {
+    fn stop(&mut self) {
+        if let Some(tx) = self.input_tx.take() {
+            tx.close().unwrap();
+            if let Some(handle) = self.runner_thread.take() {
+                handle.join().unwrap();
+            }
+        }
+    }
+
+    /// Stops the runner thread and starts a new one with `config`.
+    pub fn restart(
+        &mut self,
+        config: RunnerConfig,
+    ) -> (
+        kanal::Sender<(u8, u8)>,
+        kanal::Receiver<Vec<u8>>,
+        LNXRotation,
+        kanal::Sender<RunnerCommand>,
+    ) {
+        self.stop();
+        self.config = config;
+        self.initialize_thread()
+    }
+
+    pub fn initialize_thread(
+        &mut self,
+    ) -> (
+        kanal::Sender<(u8, u8)>,
+        kanal::Receiver<Vec<u8>>,
+        LNXRotation,
+        kanal::Sender<RunnerCommand>,
+    ) {
         let (input_tx, input_rx) = kanal::unbounded::<(u8, u8)>();
+        let (command_tx, command_rx) = (self.command_tx.clone(), self.command_rx.clone());
         let (update_display_tx, update_display_rx) = kanal::unbounded::<Vec<u8>>();
         let (rotation_tx, rotation_rx) = kanal::unbounded::<LNXRotation>();
+        self.input_tx = Some(input_tx.clone());
 
         let conf = self.config.clone();
+        if self.debugger_channels.is_none() {
+            self.debugger_channels = match (conf.gdb_port(), conf.debugger()) {
+                (Some(port), _) => Some(gdb::spawn(port)),
+                (None, true) => Some(repl::spawn(conf.symbols().clone())),
+                (None, false) => None,
+            };
+        }
+        let debugger = self
+            .debugger_channels
+            .clone()
+            .map(|(command_rx, event_tx)| {
+                Debugger::new(command_rx, event_tx, conf.symbols().clone())
+            });
+        #[cfg(feature = "comlynx_external")]
+        let comlynx_listener = match conf.netplay() {
+            Some(_) => None,
+            None => Some(
+                self.comlynx_listener
+                    .get_or_insert_with(|| {
+                        spawn_listener(conf.comlynx_endpoint().clone(), conf.comlynx_raw())
+                    })
+                    .clone(),
+            ),
+        };
 
         self.runner_thread = Some(
             std::thread::Builder::new()
-            .name("Core".to_string())
-            .spawn_with_priority(ThreadPriority::Max, move |_| {
-                #[cfg(not(feature = "comlynx_external"))]
-                let mut thread: Box<dyn RunnerThread> = match conf.comlynx() {
-                    true => Box::new(ComlynxRunnerThread::new(conf, input_rx, update_display_tx, rotation_tx)),
-                    false => Box::new(PerFrameRunnerThread::new(conf, input_rx, update_display_tx, rotation_tx)),
-                };
-                #[cfg(feature = "comlynx_external")]
-                let mut thread: Box<dyn RunnerThread> = Box::new(ComlynxRunnerThread::new(conf, input_rx, update_display_tx, rotation_tx));
-
-                trace!("Runner started.");
-                thread.initialize().unwrap_or_else(|err| {
-                    println!("Error: {}", err);
-                    std::process::exit(1);
-                });
-                thread.run();
-            })
-            .expect("Could not create the main core runner thread.")
+                .name("Core".to_string())
+                .spawn_with_priority(ThreadPriority::Max, move |_| {
+                    #[cfg(not(feature = "comlynx_external"))]
+                    let mut thread: Box<dyn RunnerThread> = match conf.comlynx() {
+                        true => Box::new(ComlynxRunnerThread::new(
+                            conf,
+                            input_rx,
+                            update_display_tx,
+                            rotation_tx,
+                            command_rx,
+                            debugger,
+                        )),
+                        false => Box::new(PerFrameRunnerThread::new(
+                            conf,
+                            input_rx,
+                            update_display_tx,
+                            rotation_tx,
+                            command_rx,
+                            debugger,
+                        )),
+                    };
+                    #[cfg(feature = "comlynx_external")]
+                    let mut thread: Box<dyn RunnerThread> = match conf.netplay() {
+                        Some(_) => Box::new(NetplayRunnerThread::new(
+                            conf,
+                            input_rx,
+                            update_display_tx,
+                            rotation_tx,
+                            command_rx,
+                        )),
+                        None => {
+                            let mut thread = ComlynxRunnerThread::new(
+                                conf,
+                                input_rx,
+                                update_display_tx,
+                                rotation_tx,
+                                command_rx,
+                                debugger,
+                            );
+                            if let Some(listener) = comlynx_listener {
+                                thread.set_comlynx_listener(listener);
+                            }
+                            Box::new(thread)
+                        }
+                    };
+
+                    trace!("Runner started.");
+                    thread.initialize().unwrap_or_else(|err| {
+                        println!("Error: {}", err);
+                        std::process::exit(1);
+                    });
+                    thread.run();
+                })
+                .expect("Could not create the main core runner thread."),
         );
 
         let rotation = rotation_rx.recv().unwrap();
-       
-        (input_tx, update_display_rx, rotation)
+
+        (input_tx, update_display_rx, rotation, command_tx)
     }
 }
@@ -7,9 +7,16 @@ use ringbuf::{
 use rodio::OutputStream;
 use std::time::{Duration, Instant};
 
-use crate::sound_source::SoundSource;
+use crate::{
+    debugger::{trace::Tracer, Debugger},
+    gamedb,
+    script::Script,
+    sound_source::SoundSource,
+};
 
-use super::{RunnerConfig, RunnerThread, CRYSTAL_FREQUENCY, SAMPLE_RATE};
+use super::{
+    RunnerCommand, RunnerConfig, RunnerHooks, RunnerThread, CRYSTAL_FREQUENCY, SAMPLE_RATE,
+};
 const TICKS_PER_AUDIO_SAMPLE: u64 = CRYSTAL_FREQUENCY as u64 / SAMPLE_RATE as u64;
 const SAMPLE_BUFFER_SIZE: usize = 2048;
 
@@ -20,10 +27,15 @@ pub(crate) struct PerFrameRunnerThread {
     input_rx: kanal::Receiver<(u8, u8)>,
     update_display_tx: kanal::Sender<Vec<u8>>,
     rotation_tx: kanal::Sender<LNXRotation>,
+    command_rx: kanal::Receiver<RunnerCommand>,
     frame_time: Duration,
     next_lcd_refresh: Instant,
     last_refresh_rate: f64,
     stream: Option<OutputStream>,
+    debugger: Option<Debugger>,
+    tracer: Option<Tracer>,
+    script: Option<Script>,
+    hooks: RunnerHooks,
 }
 
 impl PerFrameRunnerThread {
@@ -32,23 +44,31 @@ impl PerFrameRunnerThread {
         input_rx: kanal::Receiver<(u8, u8)>,
         update_display_tx: kanal::Sender<Vec<u8>>,
         rotation_tx: kanal::Sender<LNXRotation>,
+        command_rx: kanal::Receiver<RunnerCommand>,
+        debugger: Option<Debugger>,
     ) -> Self {
+        let hooks = RunnerHooks::new(&config);
         Self {
             lynx: Lynx::new(),
             config,
             input_rx,
             update_display_tx,
             rotation_tx,
+            command_rx,
             sound_tick: 0,
             frame_time: Duration::from_millis(16),
             last_refresh_rate: 0f64,
             next_lcd_refresh: Instant::now(),
             stream: None,
+            debugger,
+            tracer: None,
+            script: None,
+            hooks,
         }
     }
 
     fn sound(&mut self, sound_buffer: &mut HeapProd<i16>) {
-        if self.config.mute() {
+        if self.hooks.muted() {
             return;
         }
 
@@ -65,16 +85,54 @@ impl PerFrameRunnerThread {
 
     fn display(&mut self) {
         trace!("Display updated.");
+        self.hooks.frame(&mut self.lynx);
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.frame();
+        }
+        if let Some(script) = self.script.as_mut() {
+            script.frame(&mut self.lynx, &mut self.hooks);
+        }
         let screen = self.lynx.screen_rgba().clone();
         let _ = self.update_display_tx.try_send(screen).is_ok();
     }
 
+    fn debug(&mut self) -> bool {
+        match self.debugger.as_mut() {
+            Some(debugger) if debugger.check(&self.lynx) => {
+                let screen = self.lynx.screen_rgba().clone();
+                let _ = self.update_display_tx.try_send(screen);
+                debugger.wait(
+                    &mut self.lynx,
+                    &self.input_rx,
+                    &self.command_rx,
+                    &mut self.hooks,
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// Lets the debugger stop while the runner is paused.
+    fn debug_paused(&mut self) -> bool {
+        match self.debugger.as_mut() {
+            Some(debugger) if debugger.poll_paused(&mut self.lynx) => debugger.wait(
+                &mut self.lynx,
+                &self.input_rx,
+                &self.command_rx,
+                &mut self.hooks,
+            ),
+            _ => false,
+        }
+    }
+
     fn inputs(&mut self) -> bool {
         if self.input_rx.is_disconnected() {
             return true;
         } else if let Ok(Some((joy, sw))) = self.input_rx.try_recv() {
-            self.lynx.set_joystick_u8(joy);
-            self.lynx.set_switches_u8(sw);
+            self.hooks.input(&mut self.lynx, joy, sw);
+        }
+        while let Ok(Some(command)) = self.command_rx.try_recv() {
+            command.execute(&mut self.lynx, &mut self.hooks);
         }
         false
     }
@@ -96,7 +154,7 @@ impl RunnerThread for PerFrameRunnerThread {
         match self.config.cartridge() {
             None => panic!("A cartridge is required."),
             Some(cart) => {
-                let data = std::fs::read(cart);
+                let data = gamedb::read_cartridge(cart, self.config.bank_size());
                 if data.is_err() {
                     return Err("Couldn't not load Cartridge file.");
                 }
@@ -108,6 +166,24 @@ impl RunnerThread for PerFrameRunnerThread {
         }
 
         trace!("Cart loaded.");
+
+        if let Some(trace) = self.config.trace() {
+            match Tracer::new(trace, self.config.symbols().clone()) {
+                Ok(tracer) => self.tracer = Some(tracer),
+                Err(_) => return Err("Couldn't create the trace file."),
+            }
+        }
+
+        if let Some(script) = self.config.script() {
+            match Script::new(script, &mut self.lynx, &mut self.hooks) {
+                Ok(script) => self.script = Some(script),
+                Err(err) => {
+                    println!("{err}");
+                    return Err("Couldn't start the script.");
+                }
+            }
+        }
+
         self.rotation_tx.send(self.lynx.rotation()).unwrap();
 
         Ok(())
@@ -136,9 +212,31 @@ impl RunnerThread for PerFrameRunnerThread {
                 return;
             }
 
+            if self.hooks.paused() {
+                if self.debug_paused() {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+
+            if let Some(debugger) = self.debugger.as_mut() {
+                debugger.poll(&mut self.lynx);
+            }
+
             while !self.lynx.redraw_requested() {
                 self.lynx.tick();
                 self.sound(&mut sound_buffer);
+                self.hooks.tick(&self.lynx);
+                if self.debugger.is_some() && self.debug() {
+                    return;
+                }
+                if let Some(tracer) = self.tracer.as_mut() {
+                    tracer.tick(&self.lynx);
+                }
+                if let Some(script) = self.script.as_mut() {
+                    script.tick(&mut self.lynx, &mut self.hooks);
+                }
             }
 
             rf = self.lynx.display_refresh_rate();
@@ -1,8 +1,21 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc};
 
-use macroquad::input::KeyCode;
+use holani::suzy::registers::{Joystick, Switches};
 
-#[derive(Clone)]
+use crate::{
+    control::ControlEndpoint,
+    debugger::{symbols::Symbols, trace::TraceConfig},
+    hotkeys::{Hotkey, HotkeyMapping},
+    keycodes::KeyChord,
+    lyx::BankSizes,
+};
+
+#[cfg(feature = "comlynx_external")]
+use super::comlynx_transport::ComlynxEndpoint;
+#[cfg(feature = "comlynx_external")]
+use crate::netplay::NetplayConfig;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub(crate) enum Input {
     Up,
     Down,
@@ -13,19 +26,125 @@ pub(crate) enum Input {
     Option1,
     Option2,
     Pause,
+    TurboOutside,
+    TurboInside,
+    TurboOption1,
+    TurboOption2,
+}
+
+impl Input {
+    pub(crate) const ALL: [Input; 13] = [
+        Input::Up,
+        Input::Down,
+        Input::Left,
+        Input::Right,
+        Input::Outside,
+        Input::Inside,
+        Input::Option1,
+        Input::Option2,
+        Input::Pause,
+        Input::TurboOutside,
+        Input::TurboInside,
+        Input::TurboOption1,
+        Input::TurboOption2,
+    ];
+
+    /// Name understood by `from_str`.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Input::Up => "up",
+            Input::Down => "down",
+            Input::Left => "left",
+            Input::Right => "right",
+            Input::Outside => "outside",
+            Input::Inside => "inside",
+            Input::Option1 => "option1",
+            Input::Option2 => "option2",
+            Input::Pause => "pause",
+            Input::TurboOutside => "turbo_outside",
+            Input::TurboInside => "turbo_inside",
+            Input::TurboOption1 => "turbo_option1",
+            Input::TurboOption2 => "turbo_option2",
+        }
+    }
+
+    /// The joystick bit pulsed by a turbo button.
+    pub(crate) fn turbo(self) -> Option<Joystick> {
+        match self {
+            Input::TurboOutside => Some(Joystick::outside),
+            Input::TurboInside => Some(Joystick::inside),
+            Input::TurboOption1 => Some(Joystick::option_1),
+            Input::TurboOption2 => Some(Joystick::option_2),
+            _ => None,
+        }
+    }
+
+    /// Turbo buttons are set like the button they repeat.
+    pub(crate) fn set(self, joystick: &mut Joystick, switches: &mut Switches, pressed: bool) {
+        match self {
+            Input::Pause => switches.set(Switches::pause, pressed),
+            Input::Up => joystick.set(Joystick::up, pressed),
+            Input::Down => joystick.set(Joystick::down, pressed),
+            Input::Left => joystick.set(Joystick::left, pressed),
+            Input::Right => joystick.set(Joystick::right, pressed),
+            Input::Outside | Input::TurboOutside => joystick.set(Joystick::outside, pressed),
+            Input::Inside | Input::TurboInside => joystick.set(Joystick::inside, pressed),
+            Input::Option1 | Input::TurboOption1 => joystick.set(Joystick::option_1, pressed),
+            Input::Option2 | Input::TurboOption2 => joystick.set(Joystick::option_2, pressed),
+        }
+    }
+}
+
+impl FromStr for Input {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "up" => Ok(Input::Up),
+            "down" => Ok(Input::Down),
+            "left" => Ok(Input::Left),
+            "right" => Ok(Input::Right),
+            "outside" | "out" => Ok(Input::Outside),
+            "inside" | "in" => Ok(Input::Inside),
+            "option1" | "o1" => Ok(Input::Option1),
+            "option2" | "o2" => Ok(Input::Option2),
+            "pause" => Ok(Input::Pause),
+            "turbo_outside" | "turbo_out" => Ok(Input::TurboOutside),
+            "turbo_inside" | "turbo_in" => Ok(Input::TurboInside),
+            "turbo_option1" | "turbo_o1" => Ok(Input::TurboOption1),
+            "turbo_option2" | "turbo_o2" => Ok(Input::TurboOption2),
+            _ => Err(format!("Unknown button '{s}'.")),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub(crate) struct RunnerConfig {
     rom: Option<PathBuf>,
     cartridge: Option<PathBuf>,
-    button_mapping: HashMap<KeyCode, Input>,
+    bank_size: Option<BankSizes>,
+    button_mapping: HashMap<KeyChord, Input>,
+    hotkey_mapping: HotkeyMapping,
     linear_filter: bool,
     mute: bool,
+    debugger: bool,
+    turbo_rate: u32,
+    reset_after: Option<u32>,
+    gdb_port: Option<u16>,
+    trace: Option<TraceConfig>,
+    symbols: Arc<Symbols>,
+    script: Option<PathBuf>,
+    control: Option<ControlEndpoint>,
     #[cfg(not(feature = "comlynx_external"))]
     comlynx: bool,
     #[cfg(feature = "comlynx_external")]
-    comlynx_port: u16,
+    comlynx_endpoint: ComlynxEndpoint,
+    #[cfg(feature = "comlynx_external")]
+    comlynx_raw: bool,
+    #[cfg(feature = "comlynx_external")]
+    comlynx_lockstep: Option<u32>,
+    #[cfg(feature = "comlynx_external")]
+    netplay: Option<NetplayConfig>,
 }
 
 impl RunnerConfig {
@@ -33,13 +152,29 @@ impl RunnerConfig {
         Self {
             rom: None,
             cartridge: None,
+            bank_size: None,
             linear_filter: false,
             mute: false,
+            debugger: false,
+            turbo_rate: 2,
+            reset_after: None,
+            gdb_port: None,
+            trace: None,
+            symbols: Arc::new(Symbols::default()),
+            script: None,
+            control: None,
             #[cfg(not(feature = "comlynx_external"))]
             comlynx: false,
             #[cfg(feature = "comlynx_external")]
-            comlynx_port: 0,
-            button_mapping: HashMap::new()
+            comlynx_endpoint: ComlynxEndpoint::Tcp("0.0.0.0:0".to_string()),
+            #[cfg(feature = "comlynx_external")]
+            comlynx_raw: false,
+            #[cfg(feature = "comlynx_external")]
+            comlynx_lockstep: None,
+            #[cfg(feature = "comlynx_external")]
+            netplay: None,
+            button_mapping: HashMap::new(),
+            hotkey_mapping: Hotkey::defaults(),
         }
     }
 
@@ -59,51 +194,161 @@ impl RunnerConfig {
         self.cartridge = Some(cartridge);
     }
 
-    pub(crate) fn button_mapping(&self) -> &HashMap<KeyCode, Input> {
+    pub(crate) fn bank_size(&self) -> Option<BankSizes> {
+        self.bank_size
+    }
+
+    pub(crate) fn set_bank_size(&mut self, bank_size: Option<BankSizes>) {
+        self.bank_size = bank_size;
+    }
+
+    pub(crate) fn button_mapping(&self) -> &HashMap<KeyChord, Input> {
         &self.button_mapping
     }
 
-    pub(crate) fn set_button_mapping(&mut self, key: KeyCode, btn: Input) {
+    pub(crate) fn set_button_mapping(&mut self, key: KeyChord, btn: Input) {
         if let Some(x) = self.button_mapping.get_mut(&key) {
             *x = btn;
         } else {
             self.button_mapping.insert(key, btn);
         }
     }
-        
+
+    pub(crate) fn hotkey_mapping(&self) -> &HotkeyMapping {
+        &self.hotkey_mapping
+    }
+
+    pub(crate) fn set_hotkey_mapping(&mut self, mapping: HotkeyMapping) {
+        self.hotkey_mapping = mapping;
+    }
+
     pub(crate) fn linear_filter(&self) -> bool {
         self.linear_filter
     }
-    
+
     pub(crate) fn set_linear_filter(&mut self, linear_filter: bool) {
         self.linear_filter = linear_filter;
     }
-    
+
     pub(crate) fn mute(&self) -> bool {
         self.mute
     }
-    
+
     pub(crate) fn set_mute(&mut self, mute: bool) {
         self.mute = mute;
     }
-    
+
+    pub(crate) fn turbo_rate(&self) -> u32 {
+        self.turbo_rate
+    }
+
+    pub(crate) fn set_turbo_rate(&mut self, turbo_rate: u32) {
+        self.turbo_rate = turbo_rate;
+    }
+
+    pub(crate) fn reset_after(&self) -> Option<u32> {
+        self.reset_after
+    }
+
+    pub(crate) fn set_reset_after(&mut self, frames: Option<u32>) {
+        self.reset_after = frames;
+    }
+
+    pub(crate) fn debugger(&self) -> bool {
+        self.debugger
+    }
+
+    pub(crate) fn set_debugger(&mut self, debugger: bool) {
+        self.debugger = debugger;
+    }
+
+    pub(crate) fn gdb_port(&self) -> Option<u16> {
+        self.gdb_port
+    }
+
+    pub(crate) fn set_gdb_port(&mut self, port: Option<u16>) {
+        self.gdb_port = port;
+    }
+
+    pub(crate) fn trace(&self) -> &Option<TraceConfig> {
+        &self.trace
+    }
+
+    pub(crate) fn set_trace(&mut self, trace: Option<TraceConfig>) {
+        self.trace = trace;
+    }
+
+    pub(crate) fn symbols(&self) -> &Arc<Symbols> {
+        &self.symbols
+    }
+
+    pub(crate) fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = Arc::new(symbols);
+    }
+
+    pub(crate) fn script(&self) -> &Option<PathBuf> {
+        &self.script
+    }
+
+    pub(crate) fn set_script(&mut self, script: Option<PathBuf>) {
+        self.script = script;
+    }
+
+    pub(crate) fn control(&self) -> &Option<ControlEndpoint> {
+        &self.control
+    }
+
+    pub(crate) fn set_control(&mut self, control: Option<ControlEndpoint>) {
+        self.control = control;
+    }
+
     #[cfg(not(feature = "comlynx_external"))]
     pub(crate) fn comlynx(&self) -> bool {
         self.comlynx
     }
-        
+
     #[cfg(not(feature = "comlynx_external"))]
     pub(crate) fn set_comlynx(&mut self, comlynx: bool) {
         self.comlynx = comlynx;
     }
 
     #[cfg(feature = "comlynx_external")]
-    pub(crate) fn comlynx_port(&self) -> u16 {
-        self.comlynx_port
+    pub(crate) fn comlynx_endpoint(&self) -> &ComlynxEndpoint {
+        &self.comlynx_endpoint
+    }
+
+    #[cfg(feature = "comlynx_external")]
+    pub(crate) fn set_comlynx_endpoint(&mut self, endpoint: ComlynxEndpoint) {
+        self.comlynx_endpoint = endpoint;
+    }
+
+    #[cfg(feature = "comlynx_external")]
+    pub(crate) fn comlynx_raw(&self) -> bool {
+        self.comlynx_raw
+    }
+
+    #[cfg(feature = "comlynx_external")]
+    pub(crate) fn set_comlynx_raw(&mut self, raw: bool) {
+        self.comlynx_raw = raw;
+    }
+
+    #[cfg(feature = "comlynx_external")]
+    pub(crate) fn comlynx_lockstep(&self) -> Option<u32> {
+        self.comlynx_lockstep
     }
-    
+
+    #[cfg(feature = "comlynx_external")]
+    pub(crate) fn set_comlynx_lockstep(&mut self, window: Option<u32>) {
+        self.comlynx_lockstep = window;
+    }
+
+    #[cfg(feature = "comlynx_external")]
+    pub(crate) fn netplay(&self) -> &Option<NetplayConfig> {
+        &self.netplay
+    }
+
     #[cfg(feature = "comlynx_external")]
-    pub(crate) fn set_comlynx_port(&mut self, port: u16) {
-        self.comlynx_port = port;
+    pub(crate) fn set_netplay(&mut self, netplay: Option<NetplayConfig>) {
+        self.netplay = netplay;
     }
 }
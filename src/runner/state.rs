@@ -0,0 +1,24 @@
+//! Save states: the whole `Lynx` serialized with bincode. They are only
+//! meant to be loaded back by the same build.
+
+use holani::lynx::Lynx;
+use std::path::Path;
+
+pub(crate) fn save(lynx: &Lynx) -> Result<Vec<u8>, String> {
+    bincode::serialize(lynx).map_err(|err| format!("Couldn't save the state: {err}."))
+}
+
+pub(crate) fn load(data: &[u8]) -> Result<Lynx, String> {
+    bincode::deserialize(data).map_err(|err| format!("Couldn't load the state: {err}."))
+}
+
+pub(crate) fn save_file(lynx: &Lynx, path: &Path) -> Result<(), String> {
+    std::fs::write(path, save(lynx)?)
+        .map_err(|err| format!("Couldn't write '{}': {err}.", path.display()))
+}
+
+pub(crate) fn load_file(path: &Path) -> Result<Lynx, String> {
+    let data =
+        std::fs::read(path).map_err(|err| format!("Couldn't read '{}': {err}.", path.display()))?;
+    load(&data)
+}
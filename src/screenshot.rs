@@ -0,0 +1,19 @@
+//! PNG export of the Lynx screen.
+
+use holani::mikey::video::{LYNX_SCREEN_HEIGHT, LYNX_SCREEN_WIDTH};
+use std::{fs::File, io::BufWriter, path::Path};
+
+/// Writes an RGBA screen buffer, as sent to the display, to `path`.
+pub(crate) fn save(path: &Path, rgba: &[u8]) -> Result<(), String> {
+    let error =
+        |err: &dyn std::fmt::Display| format!("Couldn't write '{}': {err}.", path.display());
+    let file = File::create(path).map_err(|err| error(&err))?;
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(file), LYNX_SCREEN_WIDTH, LYNX_SCREEN_HEIGHT);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgba))
+        .map_err(|err| error(&err))
+}
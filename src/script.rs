@@ -0,0 +1,315 @@
+//! Rhai scripts run by the runner thread.
+//!
+//! A script defines any of these functions, called from the emulation:
+//! - `on_frame(frame)`: when a frame has been displayed.
+//! - `on_input()`: when the game reads the joystick or switches, at most once
+//!   a frame, before the read.
+//! - `on_memory(addr, write)`: before an instruction accesses one of the
+//!   addresses given to `watch`.
+//!
+//! and can call:
+//! - `peek(addr)`, `poke(addr, value)`, `frame()`
+//! - `watch(addr)`, `unwatch(addr)`
+//! - `set_buttons(joystick, switches)`, `set_button(name, pressed)` with
+//!   `up`, `down`, `left`, `right`, `outside`, `inside`, `option1`,
+//!   `option2` or `pause`
+//! - `screenshot(path)`, `save_state(path)`, `load_state(path)`
+//!
+//! The emulation is stopped while a hook runs: `peek` reads a copy of the
+//! address space taken before the first call of the frame, with the watched
+//! addresses and the joystick and switches read again before every call.
+//! Everything else is applied once the hook returns.
+
+use crate::{
+    debugger::{
+        cpu::{self, Registers},
+        opcodes::{effective_address, Access, Opcode},
+    },
+    runner::{runner_config::Input, state, RunnerHooks},
+    screenshot,
+};
+use holani::{
+    lynx::Lynx,
+    suzy::registers::{Joystick, Switches},
+};
+use rhai::{Dynamic, Engine, EvalAltResult, FuncArgs, Scope, AST, INT};
+use std::{cell::RefCell, collections::BTreeSet, path::Path, path::PathBuf, rc::Rc};
+
+const JOYSTICK: u16 = 0xFCB0;
+const SWITCHES: u16 = 0xFCB1;
+
+const ON_FRAME: &str = "on_frame";
+const ON_INPUT: &str = "on_input";
+const ON_MEMORY: &str = "on_memory";
+
+enum Action {
+    Poke(u16, u8),
+    Buttons(u8, u8),
+    Screenshot(PathBuf),
+    SaveState(PathBuf),
+    LoadState(PathBuf),
+}
+
+/// What the script functions share with the runner thread.
+#[derive(Default)]
+struct Context {
+    memory: Vec<u8>,
+    frame: u64,
+    joystick: u8,
+    switches: u8,
+    watches: BTreeSet<u16>,
+    actions: Vec<Action>,
+}
+
+fn address(addr: INT) -> Result<u16, Box<EvalAltResult>> {
+    u16::try_from(addr).map_err(|_| format!("Invalid address {addr}.").into())
+}
+
+fn byte(value: INT) -> Result<u8, Box<EvalAltResult>> {
+    u8::try_from(value).map_err(|_| format!("Invalid byte {value}.").into())
+}
+
+fn register_api(engine: &mut Engine, context: &Rc<RefCell<Context>>) {
+    let ctx = context.clone();
+    engine.register_fn(
+        "peek",
+        move |addr: INT| -> Result<INT, Box<EvalAltResult>> {
+            let addr = address(addr)?;
+            Ok(INT::from(
+                ctx.borrow()
+                    .memory
+                    .get(usize::from(addr))
+                    .copied()
+                    .unwrap_or(0),
+            ))
+        },
+    );
+    let ctx = context.clone();
+    engine.register_fn(
+        "poke",
+        move |addr: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+            let (addr, value) = (address(addr)?, byte(value)?);
+            let mut ctx = ctx.borrow_mut();
+            if let Some(byte) = ctx.memory.get_mut(usize::from(addr)) {
+                *byte = value;
+            }
+            ctx.actions.push(Action::Poke(addr, value));
+            Ok(())
+        },
+    );
+    let ctx = context.clone();
+    engine.register_fn("frame", move || -> INT {
+        INT::try_from(ctx.borrow().frame).unwrap_or(INT::MAX)
+    });
+    let ctx = context.clone();
+    engine.register_fn(
+        "watch",
+        move |addr: INT| -> Result<(), Box<EvalAltResult>> {
+            ctx.borrow_mut().watches.insert(address(addr)?);
+            Ok(())
+        },
+    );
+    let ctx = context.clone();
+    engine.register_fn(
+        "unwatch",
+        move |addr: INT| -> Result<(), Box<EvalAltResult>> {
+            ctx.borrow_mut().watches.remove(&address(addr)?);
+            Ok(())
+        },
+    );
+    let ctx = context.clone();
+    engine.register_fn(
+        "set_buttons",
+        move |joystick: INT, switches: INT| -> Result<(), Box<EvalAltResult>> {
+            let (joystick, switches) = (byte(joystick)?, byte(switches)?);
+            let mut ctx = ctx.borrow_mut();
+            ctx.joystick = joystick;
+            ctx.switches = switches;
+            ctx.actions.push(Action::Buttons(joystick, switches));
+            Ok(())
+        },
+    );
+    let ctx = context.clone();
+    engine.register_fn(
+        "set_button",
+        move |name: &str, pressed: bool| -> Result<(), Box<EvalAltResult>> {
+            let mut ctx = ctx.borrow_mut();
+            let input: Input = name.parse()?;
+            let mut joystick = Joystick::from_bits_truncate(ctx.joystick);
+            let mut switches = Switches::from_bits_truncate(ctx.switches);
+            input.set(&mut joystick, &mut switches, pressed);
+            ctx.joystick = joystick.bits();
+            ctx.switches = switches.bits();
+            let action = Action::Buttons(ctx.joystick, ctx.switches);
+            ctx.actions.push(action);
+            Ok(())
+        },
+    );
+    let ctx = context.clone();
+    engine.register_fn("screenshot", move |path: &str| {
+        ctx.borrow_mut()
+            .actions
+            .push(Action::Screenshot(path.into()));
+    });
+    let ctx = context.clone();
+    engine.register_fn("save_state", move |path: &str| {
+        ctx.borrow_mut()
+            .actions
+            .push(Action::SaveState(path.into()));
+    });
+    let ctx = context.clone();
+    engine.register_fn("load_state", move |path: &str| {
+        ctx.borrow_mut()
+            .actions
+            .push(Action::LoadState(path.into()));
+    });
+}
+
+pub(crate) struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    context: Rc<RefCell<Context>>,
+    on_frame: bool,
+    on_input: bool,
+    on_memory: bool,
+    frame: u64,
+    /// Frame the copy of the address space was taken at.
+    snapshot_frame: Option<u64>,
+    polled: bool,
+    pc: Option<u16>,
+}
+
+impl Script {
+    /// Compiles the script and runs its top level, once the cartridge is loaded.
+    pub(crate) fn new(
+        path: &Path,
+        lynx: &mut Lynx,
+        hooks: &mut RunnerHooks,
+    ) -> Result<Self, String> {
+        let context = Rc::new(RefCell::new(Context::default()));
+        let mut engine = Engine::new();
+        register_api(&mut engine, &context);
+        let ast = engine
+            .compile_file(path.to_path_buf())
+            .map_err(|err| format!("Couldn't load script '{}': {err}.", path.display()))?;
+        let defines = |name: &str| ast.iter_functions().any(|f| f.name == name);
+
+        let mut script = Self {
+            on_frame: defines(ON_FRAME),
+            on_input: defines(ON_INPUT),
+            on_memory: defines(ON_MEMORY),
+            engine,
+            ast,
+            scope: Scope::new(),
+            context,
+            frame: 0,
+            snapshot_frame: None,
+            polled: false,
+            pc: None,
+        };
+        script.snapshot(lynx, hooks);
+        script
+            .engine
+            .run_ast_with_scope(&mut script.scope, &script.ast)
+            .map_err(|err| format!("Script error: {err}."))?;
+        script.apply(lynx, hooks);
+        script.snapshot_frame = None;
+        Ok(script)
+    }
+
+    /// Called after every tick.
+    pub(crate) fn tick(&mut self, lynx: &mut Lynx, hooks: &mut RunnerHooks) {
+        if !self.on_input && !self.on_memory {
+            return;
+        }
+        let registers = Registers::read(lynx);
+        if self.pc == Some(registers.pc) {
+            return;
+        }
+        self.pc = Some(registers.pc);
+
+        let (access, addr) = {
+            let peek = |addr| cpu::peek(lynx, addr);
+            let access = Opcode::decode(peek(registers.pc)).access();
+            match effective_address(registers.pc, registers.x, registers.y, &peek) {
+                Some(addr) => (access, addr),
+                None => return,
+            }
+        };
+        let read = matches!(access, Access::Read | Access::ReadWrite);
+        if self.on_input && !self.polled && read && (addr == JOYSTICK || addr == SWITCHES) {
+            self.polled = true;
+            self.call(lynx, hooks, ON_INPUT, ());
+        }
+        if self.on_memory && self.context.borrow().watches.contains(&addr) {
+            let write = matches!(access, Access::Write | Access::ReadWrite);
+            self.call(lynx, hooks, ON_MEMORY, (INT::from(addr), write));
+        }
+    }
+
+    /// Called when a frame has been displayed.
+    pub(crate) fn frame(&mut self, lynx: &mut Lynx, hooks: &mut RunnerHooks) {
+        if self.on_frame {
+            let frame = INT::try_from(self.frame).unwrap_or(INT::MAX);
+            self.call(lynx, hooks, ON_FRAME, (frame,));
+        }
+        self.frame += 1;
+        self.polled = false;
+    }
+
+    /// Copies the address space once a frame, hooks can be called on every
+    /// instruction. `set_button` starts from the buttons held in the runner.
+    fn snapshot(&mut self, lynx: &Lynx, hooks: &RunnerHooks) {
+        let mut ctx = self.context.borrow_mut();
+        let ctx = &mut *ctx;
+        if self.snapshot_frame == Some(self.frame) {
+            for addr in ctx.watches.iter().chain(&[JOYSTICK, SWITCHES]) {
+                ctx.memory[usize::from(*addr)] = cpu::peek(lynx, *addr);
+            }
+        } else {
+            ctx.memory = (0..=u16::MAX).map(|addr| cpu::peek(lynx, addr)).collect();
+            self.snapshot_frame = Some(self.frame);
+        }
+        ctx.frame = self.frame;
+        (ctx.joystick, ctx.switches) = hooks.buttons();
+    }
+
+    fn call(&mut self, lynx: &mut Lynx, hooks: &mut RunnerHooks, name: &str, args: impl FuncArgs) {
+        self.snapshot(lynx, hooks);
+        if let Err(err) = self
+            .engine
+            .call_fn::<Dynamic>(&mut self.scope, &self.ast, name, args)
+        {
+            println!("Script error in {name}: {err}.");
+        }
+        self.apply(lynx, hooks);
+    }
+
+    /// Buttons go through the runner, like `RunnerCommand::SetButtons`, so
+    /// that turbo and the next frames keep them.
+    fn apply(&mut self, lynx: &mut Lynx, hooks: &mut RunnerHooks) {
+        let actions = std::mem::take(&mut self.context.borrow_mut().actions);
+        for action in actions {
+            let result = match action {
+                Action::Poke(addr, value) => {
+                    cpu::poke(lynx, addr, value);
+                    Ok(())
+                }
+                Action::Buttons(joystick, switches) => {
+                    hooks.input(lynx, joystick, switches);
+                    Ok(())
+                }
+                Action::Screenshot(path) => screenshot::save(&path, lynx.screen_rgba()),
+                Action::SaveState(path) => state::save_file(lynx, &path),
+                Action::LoadState(path) => hooks.load_state(lynx, &path).map(|()| {
+                    self.pc = None;
+                    self.snapshot_frame = None;
+                }),
+            };
+            if let Err(err) = result {
+                println!("Script error: {err}");
+            }
+        }
+    }
+}
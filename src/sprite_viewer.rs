@@ -0,0 +1,206 @@
+use crate::{debugger::suzy::SpriteFrame, overlay::Overlay, runner::RunnerCommand};
+use macroquad::prelude::*;
+
+const TOGGLE_KEY: KeyCode = KeyCode::F4;
+const FONT_SIZE: f32 = 16.;
+const LINE_HEIGHT: f32 = 16.;
+const MARGIN: f32 = 8.;
+const HELP: &str = "F4 close  Up/Down select  Space freeze";
+
+/// Sprite control blocks Suzy processed during the last frame, with the
+/// selected sprite drawn on its own, unscaled.
+pub(crate) struct SpriteViewer {
+    command_tx: kanal::Sender<RunnerCommand>,
+    frame_tx: kanal::Sender<SpriteFrame>,
+    frame_rx: kanal::Receiver<SpriteFrame>,
+    visible: bool,
+    requested: bool,
+    frozen: bool,
+    frame: Option<SpriteFrame>,
+    selected: usize,
+    texture: Option<Texture2D>,
+}
+
+impl SpriteViewer {
+    pub(crate) fn new(command_tx: kanal::Sender<RunnerCommand>) -> Self {
+        let (frame_tx, frame_rx) = kanal::bounded::<SpriteFrame>(1);
+        Self {
+            command_tx,
+            frame_tx,
+            frame_rx,
+            visible: false,
+            requested: false,
+            frozen: false,
+            frame: None,
+            selected: 0,
+            texture: None,
+        }
+    }
+
+    fn rows() -> usize {
+        ((screen_height() - MARGIN * 2.) / LINE_HEIGHT - 2.).max(1.) as usize
+    }
+
+    fn sprite_count(&self) -> usize {
+        self.frame.as_ref().map_or(0, |f| f.sprites.len())
+    }
+
+    fn update_texture(&mut self) {
+        self.texture = self
+            .frame
+            .as_ref()
+            .and_then(|frame| frame.sprites.get(self.selected))
+            .filter(|sprite| sprite.width > 0 && sprite.height > 0)
+            .map(|sprite| {
+                let texture = Texture2D::from_rgba8(sprite.width, sprite.height, &sprite.rgba);
+                texture.set_filter(FilterMode::Nearest);
+                texture
+            });
+    }
+}
+
+impl Overlay for SpriteViewer {
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn update(&mut self) -> bool {
+        if is_key_pressed(TOGGLE_KEY) {
+            self.visible = !self.visible;
+        }
+        if !self.visible {
+            return false;
+        }
+
+        if let Ok(Some(frame)) = self.frame_rx.try_recv() {
+            self.requested = false;
+            if !self.frozen {
+                self.frame = Some(frame);
+                self.selected = self.selected.min(self.sprite_count().saturating_sub(1));
+                self.update_texture();
+            }
+        }
+        if !self.requested && !self.frozen {
+            self.requested = self
+                .command_tx
+                .send(RunnerCommand::CaptureSprites(self.frame_tx.clone()))
+                .is_ok();
+        }
+
+        let selected = self.selected;
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1).min(self.sprite_count().saturating_sub(1));
+        }
+        if is_key_pressed(KeyCode::Space) {
+            self.frozen = !self.frozen;
+        }
+        if selected != self.selected {
+            self.update_texture();
+        }
+        true
+    }
+
+    fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        draw_rectangle(
+            0.,
+            0.,
+            screen_width(),
+            screen_height(),
+            Color::new(0., 0., 0., 0.85),
+        );
+        let text_y = |row: usize| MARGIN + LINE_HEIGHT * (row as f32 + 1.) - 4.;
+
+        let Some(frame) = &self.frame else {
+            draw_text(HELP, MARGIN, text_y(0), FONT_SIZE, WHITE);
+            draw_text("Waiting for a frame...", MARGIN, text_y(1), FONT_SIZE, GRAY);
+            return;
+        };
+        let state = if self.frozen { "frozen" } else { "live" };
+        draw_text(
+            &format!(
+                "{HELP}    {} sprite(s), collision depth {:02X}, {state}",
+                frame.sprites.len(),
+                frame.collision_depth
+            ),
+            MARGIN,
+            text_y(0),
+            FONT_SIZE,
+            WHITE,
+        );
+        draw_text(
+            "  #  SCB    X     Y     W    H    size       type               bpp coll  palette",
+            MARGIN,
+            text_y(1),
+            FONT_SIZE,
+            GRAY,
+        );
+
+        let first = self.selected.saturating_sub(Self::rows() / 2);
+        for (row, (i, sprite)) in frame
+            .sprites
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(Self::rows())
+            .enumerate()
+        {
+            let scb = &sprite.scb;
+            let collision = if scb.collides() {
+                format!("{:X}", scb.collision_number())
+            } else {
+                "-".to_string()
+            };
+            let palette: String = scb.palette.iter().map(|pen| format!("{pen:X}")).collect();
+            let color = match (i == self.selected, scb.skipped()) {
+                (true, _) => YELLOW,
+                (false, true) => GRAY,
+                (false, false) => WHITE,
+            };
+            draw_text(
+                &format!(
+                    "{i:3}  ${:04X} {:5} {:5} {:4} {:4} {:04X}x{:04X}  {:<18} {}   {:<4}  {palette}",
+                    scb.addr,
+                    scb.x,
+                    scb.y,
+                    sprite.width,
+                    sprite.height,
+                    scb.hsize,
+                    scb.vsize,
+                    scb.kind(),
+                    scb.bpp(),
+                    collision,
+                ),
+                MARGIN,
+                text_y(row + 2),
+                FONT_SIZE,
+                color,
+            );
+        }
+
+        if let Some(texture) = &self.texture {
+            let scale = (256. / texture.width().max(texture.height()))
+                .clamp(1., 8.)
+                .floor();
+            let (width, height) = (texture.width() * scale, texture.height() * scale);
+            let x = screen_width() - width - MARGIN;
+            let y = screen_height() - height - MARGIN;
+            draw_rectangle_lines(x - 1., y - 1., width + 2., height + 2., 1., GRAY);
+            draw_texture_ex(
+                texture,
+                x,
+                y,
+                WHITE,
+                DrawTextureParams {
+                    dest_size: Some(vec2(width, height)),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+}